@@ -0,0 +1,51 @@
+/**
+ * admin_commands
+ * This sample plugin registers commands through the command layer, which checks
+ * permissions and cooldowns before running them, and answers !help automatically.
+ */
+use std::time::Duration;
+
+use omegga::{
+    chat::RichText,
+    commands::{CommandSpec, Commands},
+    events::Event,
+    panics, Omegga,
+};
+
+#[tokio::main]
+async fn main() {
+    let omegga = Omegga::new();
+    panics::install_hook(&omegga);
+    let mut rx = omegga.spawn();
+
+    let mut commands = Commands::new();
+    commands.add(
+        CommandSpec::new("clearall", |ctx| async move {
+            ctx.omegga.clear_all_bricks(false);
+        })
+        .description("Clears every brick on the server.")
+        .permission("Bricks.ClearAll")
+        .global_cooldown(Duration::from_secs(30)),
+    );
+    commands.add(
+        CommandSpec::new("echo", |ctx| async move {
+            ctx.reply(RichText::text(ctx.args.join(" ")));
+        })
+        .description("Repeats a message back to you.")
+        .arg("message", "The message to repeat.", true)
+        .example("!echo hello")
+        .cooldown(Duration::from_secs(5)),
+    );
+
+    while let Some(event) = rx.recv().await {
+        if commands.handle(&omegga, &event) {
+            continue;
+        }
+
+        match event {
+            Event::Init { id, .. } => commands.register(&omegga, id),
+            Event::Stop { id, .. } => omegga.shutdown(id, Duration::from_secs(5)).await,
+            _ => (),
+        }
+    }
+}
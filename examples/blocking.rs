@@ -0,0 +1,26 @@
+/**
+ * blocking
+ * This sample plugin uses the blocking interface, without async.
+ */
+use omegga::{blocking::Omegga, events::Event};
+
+fn main() {
+    let omegga = Omegga::new();
+
+    for event in omegga.spawn() {
+        match event {
+            Event::Init { id, .. } => omegga.write_response(id, None, None),
+            Event::Stop { id, .. } => omegga.write_response(id, None, None),
+            Event::ChatCommand {
+                player, command, ..
+            } if command == "players" => {
+                let players = omegga.get_players().unwrap_or_default();
+                omegga.whisper(
+                    player,
+                    format!("There are {} players online.", players.len()),
+                );
+            }
+            _ => (),
+        }
+    }
+}
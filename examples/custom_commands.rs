@@ -2,11 +2,16 @@
  * custom_commands
  * This sample plugin allows users to create custom commands with !new-cmd <cmd-name> <content>.
  * Other users can use these commands with !<cmd-name>, and it will display <content>
- * Commands can then be removed with !del-cmd <cmd-name>, and all can be viewed with !ls-cmds.
+ * Commands can then be removed with !del-cmd <cmd-name>, and all can be viewed with
+ * !ls-cmds [page | next | prev].
  */
 use std::collections::HashMap;
 
-use omegga::{events::Event, Omegga};
+use omegga::{
+    chat::{self, Paginator, RichText},
+    events::Event,
+    Omegga,
+};
 
 #[tokio::main]
 async fn main() {
@@ -14,6 +19,7 @@ async fn main() {
     let mut rx = omegga.spawn();
 
     let mut commands = HashMap::new();
+    let paginator = Paginator::new(10);
 
     while let Some(event) = rx.recv().await {
         match event {
@@ -23,40 +29,65 @@ async fn main() {
             }
             Event::Stop { id, .. } => omegga.write_response(id, None, None),
 
-            Event::ChatCommand { command, args, .. } => match command.as_str() {
+            Event::ChatCommand {
+                player,
+                command,
+                args,
+            } => match command.as_str() {
                 "new-cmd" => {
                     let mut args = args.into_iter();
                     let name = args.next().unwrap();
                     let content = args.collect::<Vec<_>>().join(" ");
 
-                    if let None = commands.insert(name.clone(), content) {
-                        omegga.broadcast(format!("OK, created the custom command {}.", name));
+                    if commands.insert(name.clone(), content).is_none() {
+                        omegga.broadcast(RichText::text(format!(
+                            "OK, created the custom command {}.",
+                            name
+                        )));
                     } else {
-                        omegga.broadcast(format!("OK, overwrote that existing custom command."));
+                        omegga.broadcast("OK, overwrote that existing custom command.");
                     }
                 }
                 "del-cmd" => {
                     let name = args.into_iter().next().unwrap();
-                    if let Some(_) = commands.remove(&name) {
-                        omegga.broadcast(format!("OK, removed the custom command {}.", name));
+                    if commands.remove(&name).is_some() {
+                        omegga.broadcast(RichText::text(format!(
+                            "OK, removed the custom command {}.",
+                            name
+                        )));
                     } else {
                         omegga.broadcast("That custom command didn't exist.");
                     }
                 }
                 "ls-cmds" => {
-                    omegga.broadcast("<b>Custom Commands</>");
-                    for chunk in commands.keys().collect::<Vec<&String>>().chunks(5) {
-                        let names = chunk
-                            .iter()
-                            .map(|cmd| format!("<code>!{}</>", cmd))
-                            .collect::<Vec<_>>()
-                            .join(", ");
-                        omegga.broadcast(names);
-                    }
+                    let mut names = commands.keys().collect::<Vec<_>>();
+                    names.sort();
+
+                    let page = match args.first().map(String::as_str) {
+                        Some("next") => paginator.next(player.as_str(), &names),
+                        Some("prev") => paginator.prev(player.as_str(), &names),
+                        Some(page) => {
+                            paginator.page(player.as_str(), &names, page.parse().unwrap_or(1))
+                        }
+                        None => paginator.page(player.as_str(), &names, 1),
+                    };
+
+                    let names = page
+                        .items
+                        .iter()
+                        .map(|cmd| format!("<code>!{}</>", chat::escape(cmd)))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+
+                    omegga.whisper(
+                        &player,
+                        format!("<b>Custom Commands</> ({})", page.header()),
+                    );
+                    omegga.whisper_long(player, names);
                 }
                 cmd => {
                     if let Some(content) = commands.get(cmd) {
-                        omegga.broadcast(content);
+                        omegga.broadcast(RichText::text(content));
                     }
                 }
             },
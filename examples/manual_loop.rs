@@ -0,0 +1,30 @@
+/**
+ * manual_loop
+ * This sample plugin doesn't use an async runtime. It reads messages from stdin itself
+ * and hands them to Omegga to decode.
+ */
+use std::io::{stdin, BufRead};
+
+use omegga::{chat, events::Event, Omegga};
+
+fn main() {
+    let omegga = Omegga::new();
+
+    for line in stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+
+        match omegga.handle_line(&line) {
+            Some(Event::Init { id, .. }) => omegga.write_response(id, None, None),
+            Some(Event::Stop { id, .. }) => omegga.write_response(id, None, None),
+            Some(Event::ChatCommand {
+                player, command, ..
+            }) if command == "ping" => {
+                omegga.broadcast(format!("Pong to {}!", chat::escape(&player)))
+            }
+            _ => (),
+        }
+    }
+}
@@ -14,13 +14,10 @@ async fn main() {
             Event::Stop { id, .. } => omegga.write_response(id, None, None),
 
             // Listen to commands sent to the plugin...
+            // When the command matches `ping`, send `Pong!`
             Event::Command {
                 player, command, ..
-            } => match command.as_str() {
-                // When the command matches `ping`, send `Pong!`
-                "ping" => omegga.whisper(player, "Pong!"),
-                _ => (),
-            },
+            } if command == "ping" => omegga.whisper(player, "Pong!"),
 
             _ => (),
         }
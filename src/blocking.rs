@@ -0,0 +1,193 @@
+use std::{future::Future, ops::Deref};
+
+use serde_json::Value;
+use tokio::runtime::{Builder, Runtime};
+
+use crate::{
+    events::Event,
+    geometry::Vec3,
+    resources::{
+        BanList, Environment, Minigame, Permissions, Player, PlayerPosition, Plugin, RoleSetup,
+    },
+    EventReceiver, ResponseError,
+};
+
+/// A blocking interface to Omegga, for plugins and scripts that don't need async.
+///
+/// This owns a Tokio runtime, so no runtime needs to be set up to use it.
+/// Methods that don't wait for a response, like [`broadcast`](crate::Omegga::broadcast),
+/// are available through [`Deref`]. Any async method can be run with
+/// [`block_on`](Omegga::block_on). Background tasks, like scheduled jobs, commands,
+/// chat queues and position trackers, run on the owned runtime.
+///
+/// The blocking methods must not be called from within an async context.
+pub struct Omegga {
+    inner: crate::Omegga,
+    runtime: Runtime,
+}
+
+/// A blocking iterator over events, from [`Omegga::spawn`].
+pub struct Events {
+    rx: EventReceiver,
+}
+
+impl Iterator for Events {
+    type Item = Event;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rx.blocking_recv()
+    }
+}
+
+impl Default for Omegga {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Deref for Omegga {
+    type Target = crate::Omegga;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+impl Omegga {
+    /// Create a new blocking Omegga instance and its runtime.
+    ///
+    /// # Panics
+    ///
+    /// This panics if the runtime can't be created.
+    pub fn new() -> Self {
+        let runtime = Builder::new_multi_thread()
+            .worker_threads(1)
+            .enable_all()
+            .build()
+            .expect("failed to create runtime");
+
+        // created in the runtime, so its background tasks are spawned there
+        let inner = runtime.block_on(async { crate::Omegga::new() });
+        Self { inner, runtime }
+    }
+
+    /// Get the async Omegga instance this wraps.
+    pub fn inner(&self) -> &crate::Omegga {
+        &self.inner
+    }
+
+    /// Run a future to completion on the runtime, like an async [`crate::Omegga`] method.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+
+    /// Spawn the listener, returning a blocking iterator over events.
+    pub fn spawn(&self) -> Events {
+        Events {
+            rx: self.inner.spawn(),
+        }
+    }
+
+    /// Request a response from the RPC server, blocking until it arrives.
+    pub fn request(
+        &self,
+        method: impl Into<String>,
+        params: Option<Value>,
+    ) -> Result<Option<Value>, ResponseError> {
+        self.block_on(self.inner.request(method, params))
+    }
+
+    /// Gets an object from the store.
+    pub fn store_get(&self, key: impl Into<String>) -> Result<Option<Value>, ResponseError> {
+        self.block_on(self.inner.store_get(key))
+    }
+
+    /// Deletes an object from the store.
+    pub fn store_delete(&self, key: impl Into<String>) {
+        self.block_on(self.inner.store_delete(key))
+    }
+
+    /// Gets a list of keys in the store.
+    pub fn store_keys(&self) -> Result<Vec<String>, ResponseError> {
+        self.block_on(self.inner.store_keys())
+    }
+
+    /// Gets a list of all players.
+    pub fn get_players(&self) -> Result<Vec<Player>, ResponseError> {
+        self.block_on(self.inner.get_players())
+    }
+
+    /// Gets a list of all player positions.
+    pub fn get_all_player_positions(&self) -> Result<Vec<PlayerPosition>, ResponseError> {
+        self.block_on(self.inner.get_all_player_positions())
+    }
+
+    /// Get a player.
+    pub fn get_player(&self, target: impl Into<String>) -> Result<Option<Player>, ResponseError> {
+        self.block_on(self.inner.get_player(target))
+    }
+
+    /// Get a player's roles.
+    pub fn get_player_roles(
+        &self,
+        target: impl Into<String>,
+    ) -> Result<Option<Vec<String>>, ResponseError> {
+        self.block_on(self.inner.get_player_roles(target))
+    }
+
+    /// Get a player's permissions.
+    pub fn get_player_permissions(
+        &self,
+        target: impl Into<String>,
+    ) -> Result<Option<Permissions>, ResponseError> {
+        self.block_on(self.inner.get_player_permissions(target))
+    }
+
+    /// Get a player's position.
+    pub fn get_player_position(
+        &self,
+        target: impl Into<String>,
+    ) -> Result<Option<Vec3>, ResponseError> {
+        self.block_on(self.inner.get_player_position(target))
+    }
+
+    /// Get the role setup.
+    pub fn get_role_setup(&self) -> Result<Option<RoleSetup>, ResponseError> {
+        self.block_on(self.inner.get_role_setup())
+    }
+
+    /// Get the ban list.
+    pub fn get_ban_list(&self) -> Result<Option<BanList>, ResponseError> {
+        self.block_on(self.inner.get_ban_list())
+    }
+
+    /// Get a list of the server's saves.
+    pub fn get_saves(&self) -> Result<Vec<String>, ResponseError> {
+        self.block_on(self.inner.get_saves())
+    }
+
+    /// Save bricks to a named save.
+    pub fn save_bricks(&self, name: impl Into<String>) -> Result<(), ResponseError> {
+        self.block_on(self.inner.save_bricks(name))
+    }
+
+    /// Changes the map.
+    pub fn change_map(&self, map: impl Into<String>) -> Result<(), ResponseError> {
+        self.block_on(self.inner.change_map(map))
+    }
+
+    /// Get every minigame on the server.
+    pub fn get_minigames(&self) -> Result<Vec<Minigame>, ResponseError> {
+        self.block_on(self.inner.get_minigames())
+    }
+
+    /// Get the current environment.
+    pub fn get_environment_data(&self) -> Result<Option<Environment>, ResponseError> {
+        self.block_on(self.inner.get_environment_data())
+    }
+
+    /// Get a plugin.
+    pub fn get_plugin(&self, target: impl Into<String>) -> Result<Option<Plugin>, ResponseError> {
+        self.block_on(self.inner.get_plugin(target))
+    }
+}
@@ -0,0 +1,588 @@
+use std::fmt;
+
+use dashmap::DashMap;
+
+use crate::resources::Colorize;
+
+/// Characters the Brickadia chat renderer treats as markup, and the entities
+/// they are replaced with when escaped.
+const ESCAPES: &[(char, &str)] = &[
+    ('<', "&lt;"),
+    ('>', "&gt;"),
+    ('*', "&#42;"),
+    ('_', "&#95;"),
+    ('~', "&#126;"),
+    ('`', "&#96;"),
+    ('[', "&#91;"),
+    (']', "&#93;"),
+    ('\\', "&#92;"),
+    (';', "&#59;"),
+];
+
+/// Escape a string so that it is displayed verbatim in chat.
+///
+/// Any tags (`<color>`, `<size>`, `<b>`, `<link>`, `<emoji>`, ...) and markdown-style
+/// formatting (`**bold**`, `*italics*`, `[text](link)`, ...) are neutralized.
+pub fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match ESCAPES.iter().find(|(from, _)| *from == c) {
+            Some((_, to)) => out.push_str(to),
+            None => out.push(c),
+        }
+    }
+    out
+}
+
+/// A chunk of chat markup.
+///
+/// Text added to a `RichText` is escaped by default, so player-supplied content can
+/// be passed through safely. Use [`raw`](RichText::raw) to opt out for trusted markup.
+/// `RichText` implements [`Colorize`], and converts into a `String`, so it can be passed
+/// directly to [`broadcast`](crate::Omegga::broadcast), [`whisper`](crate::Omegga::whisper), etc.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RichText(String);
+
+impl RichText {
+    /// Create an empty `RichText`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a `RichText` from escaped text.
+    pub fn text(text: impl AsRef<str>) -> Self {
+        Self(escape(text.as_ref()))
+    }
+
+    /// Create a `RichText` from trusted markup. The markup is **not** escaped.
+    pub fn raw(markup: impl Into<String>) -> Self {
+        Self(markup.into())
+    }
+
+    /// Append escaped text.
+    pub fn push_text(mut self, text: impl AsRef<str>) -> Self {
+        self.0.push_str(&escape(text.as_ref()));
+        self
+    }
+
+    /// Append trusted markup. The markup is **not** escaped.
+    pub fn push_raw(mut self, markup: impl AsRef<str>) -> Self {
+        self.0.push_str(markup.as_ref());
+        self
+    }
+
+    /// Append another `RichText`.
+    pub fn push(mut self, other: RichText) -> Self {
+        self.0.push_str(&other.0);
+        self
+    }
+
+    /// Get the underlying markup.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Consume the `RichText`, returning the underlying markup.
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl Colorize for RichText {
+    fn bold(self) -> Self {
+        Self(self.0.bold())
+    }
+
+    fn italics(self) -> Self {
+        Self(self.0.italics())
+    }
+
+    fn color(self, code: &str) -> Self {
+        Self(self.0.color(code))
+    }
+
+    fn hyperlink(self, link: &str) -> Self {
+        Self(self.0.hyperlink(link))
+    }
+
+    fn size(self, size: i32) -> Self {
+        Self(self.0.size(size))
+    }
+}
+
+impl From<&str> for RichText {
+    fn from(text: &str) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<String> for RichText {
+    fn from(text: String) -> Self {
+        Self::text(text)
+    }
+}
+
+impl From<RichText> for String {
+    fn from(text: RichText) -> Self {
+        text.0
+    }
+}
+
+impl AsRef<str> for RichText {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for RichText {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A conservative maximum length for a single line of chat, in bytes of markup.
+pub const MAX_LINE_LENGTH: usize = 240;
+
+/// A conservative maximum length for a middle print, in bytes of markup, including the
+/// `<br>`s between its lines.
+pub const MAX_MIDDLE_PRINT_LENGTH: usize = 4 * MAX_LINE_LENGTH;
+
+/// A piece of chat markup, as seen by [`split`].
+enum Token<'a> {
+    /// An opening tag, like `<color="f00">`.
+    Open(&'a str),
+    /// A closing tag, like `</>`.
+    Close(&'a str),
+    /// A run of whitespace.
+    Space(&'a str),
+    /// A word, made of characters and entities (like `&lt;`) that can't be split.
+    Word(Vec<&'a str>),
+}
+
+/// Get the length of the tag or entity at the start of `text`, if there is one.
+fn markup_len(text: &str) -> Option<usize> {
+    if text.starts_with('<') {
+        text.find('>').map(|i| i + 1)
+    } else if text.starts_with('&') {
+        text.find(';')
+            .filter(|&i| i > 1 && text[1..i].chars().all(|c| c == '#' || c.is_alphanumeric()))
+            .map(|i| i + 1)
+    } else {
+        None
+    }
+}
+
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let mut tokens = vec![];
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let len = match markup_len(rest) {
+            Some(len) if c == '<' => {
+                let tag = &rest[..len];
+                tokens.push(if tag.starts_with("</") {
+                    Token::Close(tag)
+                } else {
+                    Token::Open(tag)
+                });
+                len
+            }
+            _ if c.is_whitespace() => {
+                let len = rest
+                    .find(|c: char| !c.is_whitespace())
+                    .unwrap_or(rest.len());
+                tokens.push(Token::Space(&rest[..len]));
+                len
+            }
+            _ => {
+                let mut atoms = vec![];
+                let mut len = 0;
+                while let Some(c) = rest[len..].chars().next() {
+                    let atom_len = match markup_len(&rest[len..]) {
+                        Some(_) if c == '<' => break,
+                        Some(atom_len) => atom_len,
+                        None if c.is_whitespace() => break,
+                        None => c.len_utf8(),
+                    };
+                    atoms.push(&rest[len..len + atom_len]);
+                    len += atom_len;
+                }
+                tokens.push(Token::Word(atoms));
+                len
+            }
+        };
+        rest = &rest[len..];
+    }
+    tokens
+}
+
+/// Builds the lines for [`split`], carrying open tags across line breaks.
+struct Splitter<'a> {
+    max_len: usize,
+    lines: Vec<String>,
+    line: String,
+    /// Whether `line` contains anything other than re-opened tags.
+    dirty: bool,
+    /// Tags that have been written to `line`, and are still open.
+    open: Vec<&'a str>,
+    /// Tags that have been opened, but not yet written, so that a line never ends
+    /// with an empty tag.
+    pending: Vec<&'a str>,
+}
+
+impl<'a> Splitter<'a> {
+    fn fits(&self, len: usize) -> bool {
+        let pending = self
+            .pending
+            .iter()
+            .map(|t| t.len() + "</>".len())
+            .sum::<usize>();
+        self.line.len() + pending + len + self.open.len() * "</>".len() <= self.max_len
+    }
+
+    fn flush(&mut self) {
+        if !self.dirty {
+            return;
+        }
+
+        let mut line = std::mem::take(&mut self.line);
+        line.truncate(line.trim_end().len());
+        for _ in &self.open {
+            line.push_str("</>");
+        }
+        self.lines.push(line);
+
+        self.line = self.open.concat();
+        self.dirty = false;
+    }
+
+    fn open(&mut self, tag: &'a str) {
+        self.pending.push(tag);
+    }
+
+    fn close(&mut self, tag: &str) {
+        // an empty tag can be dropped entirely
+        if self.pending.pop().is_none() && self.open.pop().is_some() {
+            self.line.push_str(tag);
+        }
+    }
+
+    fn space(&mut self, space: &str) {
+        if self.dirty && self.fits(space.len()) {
+            self.line.push_str(space);
+        } else {
+            self.flush();
+        }
+    }
+
+    fn push(&mut self, text: &str) {
+        if !self.fits(text.len()) {
+            self.flush();
+        }
+        for tag in self.pending.drain(..) {
+            self.line.push_str(tag);
+            self.open.push(tag);
+        }
+        self.line.push_str(text);
+        self.dirty = true;
+    }
+
+    fn word(&mut self, atoms: &[&str]) {
+        let word = atoms.concat();
+        if !self.fits(word.len()) {
+            self.flush();
+        }
+        if self.fits(word.len()) {
+            self.push(&word);
+            return;
+        }
+
+        // the word is too long to fit on any line, so break it up
+        for atom in atoms {
+            self.push(atom);
+        }
+    }
+}
+
+/// Split chat markup into lines no longer than `max_len` bytes.
+///
+/// Lines are broken at whitespace where possible. Tags and entities are never split,
+/// and tags that are open at a line break are closed at the end of the line and
+/// re-opened at the start of the next one.
+pub fn split(text: &str, max_len: usize) -> Vec<String> {
+    let mut splitter = Splitter {
+        max_len,
+        lines: vec![],
+        line: String::new(),
+        dirty: false,
+        open: vec![],
+        pending: vec![],
+    };
+
+    for token in tokenize(text) {
+        match token {
+            Token::Open(tag) => splitter.open(tag),
+            Token::Close(tag) => splitter.close(tag),
+            Token::Space(space) => splitter.space(space),
+            Token::Word(atoms) => splitter.word(&atoms),
+        }
+    }
+
+    splitter.flush();
+    splitter.lines
+}
+
+/// A page of items, returned from a [`Paginator`].
+#[derive(Debug, Clone, Copy)]
+pub struct Page<'a, T> {
+    /// The page number, starting at 1.
+    pub number: usize,
+    /// The total number of pages. Always at least 1.
+    pub total: usize,
+    /// The items on this page.
+    pub items: &'a [T],
+}
+
+impl<'a, T> Page<'a, T> {
+    /// Whether there is a page after this one.
+    pub fn has_next(&self) -> bool {
+        self.number < self.total
+    }
+
+    /// Whether there is a page before this one.
+    pub fn has_prev(&self) -> bool {
+        self.number > 1
+    }
+
+    /// A short header for the page, like `Page 2/5`.
+    pub fn header(&self) -> String {
+        format!("Page {}/{}", self.number, self.total)
+    }
+}
+
+/// Splits lists of items into pages, remembering the last page each player viewed,
+/// for `!cmd page 2` or `!cmd next` style commands.
+#[derive(Debug)]
+pub struct Paginator {
+    per_page: usize,
+    pages: DashMap<String, usize>,
+}
+
+impl Paginator {
+    /// Create a new paginator showing `per_page` items per page.
+    pub fn new(per_page: usize) -> Self {
+        Self {
+            per_page: per_page.max(1),
+            pages: DashMap::new(),
+        }
+    }
+
+    /// The number of items per page.
+    pub fn per_page(&self) -> usize {
+        self.per_page
+    }
+
+    /// The total number of pages for a list of `len` items.
+    pub fn total_pages(&self, len: usize) -> usize {
+        len.div_ceil(self.per_page).max(1)
+    }
+
+    /// Get a specific page (starting at 1) for a player. Out-of-range pages are clamped.
+    pub fn page<'a, T>(
+        &self,
+        player: impl Into<String>,
+        items: &'a [T],
+        page: usize,
+    ) -> Page<'a, T> {
+        let total = self.total_pages(items.len());
+        let number = page.clamp(1, total);
+        self.pages.insert(player.into(), number);
+
+        let start = ((number - 1) * self.per_page).min(items.len());
+        let end = (start + self.per_page).min(items.len());
+        Page {
+            number,
+            total,
+            items: &items[start..end],
+        }
+    }
+
+    /// Get the page the player last viewed, or the first page.
+    pub fn current<'a, T>(&self, player: impl Into<String>, items: &'a [T]) -> Page<'a, T> {
+        let player = player.into();
+        let page = self.last_page(&player).unwrap_or(1);
+        self.page(player, items, page)
+    }
+
+    /// Get the page after the one the player last viewed.
+    pub fn next<'a, T>(&self, player: impl Into<String>, items: &'a [T]) -> Page<'a, T> {
+        let player = player.into();
+        let page = self.last_page(&player).map_or(1, |p| p + 1);
+        self.page(player, items, page)
+    }
+
+    /// Get the page before the one the player last viewed.
+    pub fn prev<'a, T>(&self, player: impl Into<String>, items: &'a [T]) -> Page<'a, T> {
+        let player = player.into();
+        let page = self.last_page(&player).map_or(1, |p| p.saturating_sub(1));
+        self.page(player, items, page)
+    }
+
+    /// The page a player last viewed, if any.
+    pub fn last_page(&self, player: &str) -> Option<usize> {
+        self.pages.get(player).map(|p| *p)
+    }
+
+    /// Forget the page a player last viewed.
+    pub fn reset(&self, player: &str) {
+        self.pages.remove(player);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `markup` has no characters the chat renderer would treat as formatting,
+    /// other than the entities produced by [`escape`].
+    fn is_inert(markup: &str) -> bool {
+        let decoded = ESCAPES
+            .iter()
+            .fold(markup.to_string(), |s, (_, entity)| s.replace(entity, ""));
+        !decoded.contains(|c| ESCAPES.iter().any(|(from, _)| *from == c))
+    }
+
+    #[test]
+    fn escapes_tags() {
+        let cases = [
+            ("<color=\"f00\">red</>", "&lt;color=\"f00\"&gt;red&lt;/&gt;"),
+            ("<size=\"40\">big</>", "&lt;size=\"40\"&gt;big&lt;/&gt;"),
+            ("<b>bold</>", "&lt;b&gt;bold&lt;/&gt;"),
+            ("<i>italic</>", "&lt;i&gt;italic&lt;/&gt;"),
+            (
+                "<link=\"https://example.com\">x</>",
+                "&lt;link=\"https://example.com\"&gt;x&lt;/&gt;",
+            ),
+            ("<emoji>skull</>", "&lt;emoji&gt;skull&lt;/&gt;"),
+            ("<code>x</>", "&lt;code&gt;x&lt;/&gt;"),
+        ];
+        for (text, escaped) in cases {
+            assert_eq!(escape(text), escaped);
+            assert!(is_inert(&escape(text)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn escapes_markdown() {
+        let cases = [
+            ("**bold**", "&#42;&#42;bold&#42;&#42;"),
+            ("*italic*", "&#42;italic&#42;"),
+            ("__underline__", "&#95;&#95;underline&#95;&#95;"),
+            ("~~strike~~", "&#126;&#126;strike&#126;&#126;"),
+            ("`code`", "&#96;code&#96;"),
+            (
+                "[text](https://example.com)",
+                "&#91;text&#93;(https://example.com)",
+            ),
+            ("\\*not escaped\\*", "&#92;&#42;not escaped&#92;&#42;"),
+        ];
+        for (text, escaped) in cases {
+            assert_eq!(escape(text), escaped);
+            assert!(is_inert(&escape(text)), "{}", text);
+        }
+    }
+
+    #[test]
+    fn escapes_entities() {
+        // breaking the `;` keeps entities from being decoded, so they show as typed
+        assert_eq!(escape("&lt;b&gt;"), "&lt&#59;b&gt&#59;");
+        assert_eq!(escape("&#42;"), "&#42&#59;");
+        assert_eq!(escape("fish & chips"), "fish & chips");
+    }
+
+    #[test]
+    fn leaves_plain_text() {
+        assert_eq!(
+            escape("hello, world! 1 + 1 = 2 (ok?)"),
+            "hello, world! 1 + 1 = 2 (ok?)"
+        );
+        assert_eq!(escape("ünïcödé 🎉"), "ünïcödé 🎉");
+    }
+
+    #[test]
+    fn rich_text_escapes_by_default() {
+        let text = "<color=\"f00\">**hi**</>";
+        assert_eq!(RichText::text(text).as_str(), escape(text));
+        assert_eq!(RichText::from(text).as_str(), escape(text));
+        assert_eq!(RichText::new().push_text(text).as_str(), escape(text));
+    }
+
+    #[test]
+    fn rich_text_raw_opts_out() {
+        let markup = "<color=\"f00\">**hi**</>";
+        assert_eq!(RichText::raw(markup).as_str(), markup);
+        assert_eq!(RichText::new().push_raw(markup).as_str(), markup);
+
+        let text = RichText::raw("<b>")
+            .push_text("<i>")
+            .push_raw("</>")
+            .push(RichText::text("*"));
+        assert_eq!(text.into_string(), "<b>&lt;i&gt;</>&#42;");
+    }
+
+    #[test]
+    fn rich_text_colorize_wraps_escaped_text() {
+        assert_eq!(
+            RichText::text("<b>").color("f00").as_str(),
+            "<color=\"f00\">&lt;b&gt;</>"
+        );
+        assert_eq!(
+            RichText::text("a*b").size(20).as_str(),
+            "<size=\"20\">a&#42;b</>"
+        );
+        assert_eq!(RichText::text("x").bold().italics().as_str(), "***x***");
+        assert_eq!(
+            RichText::text("[x]")
+                .hyperlink("https://example.com")
+                .as_str(),
+            "[&#91;x&#93;](https://example.com)"
+        );
+    }
+
+    #[test]
+    fn splits_without_breaking_markup() {
+        let text = format!("<color=\"f00\">{}</> &lt;done&gt;", "word ".repeat(20));
+        let lines = split(&text, 40);
+        assert!(lines.len() > 1);
+        for line in &lines {
+            assert!(line.len() <= 40, "{}", line);
+            assert_eq!(line.matches('<').count() % 2, 0, "{}", line);
+        }
+        assert!(lines[0].starts_with("<color=\"f00\">word"));
+        assert!(lines[1].starts_with("<color=\"f00\">word"));
+        assert!(lines.last().unwrap().ends_with("&lt;done&gt;"));
+        assert_eq!(split("short", 40), ["short"]);
+    }
+
+    #[test]
+    fn paginates_per_player() {
+        let items = (1..=25).collect::<Vec<_>>();
+        let paginator = Paginator::new(10);
+
+        let page = paginator.next("a", &items);
+        assert_eq!((page.number, page.total, page.items.len()), (1, 3, 10));
+        assert_eq!(paginator.next("a", &items).number, 2);
+        assert_eq!(paginator.next("a", &items).items, &items[20..]);
+        // clamped to the last page
+        assert_eq!(paginator.next("a", &items).number, 3);
+        assert_eq!(paginator.prev("a", &items).number, 2);
+
+        // other players have their own page
+        assert_eq!(paginator.current("b", &items).number, 1);
+        assert_eq!(paginator.page("b", &items, 0).number, 1);
+        assert_eq!(paginator.page("b", &items, 99).number, 3);
+
+        paginator.reset("a");
+        assert_eq!(paginator.last_page("a"), None);
+        assert_eq!(paginator.page("a", &[] as &[i32], 2).total, 1);
+    }
+}
@@ -0,0 +1,892 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+
+use crate::{
+    chat::RichText,
+    events::Event,
+    resources::{Command, CommandArg, Permissions, Player},
+    rpc, Omegga, ResponseError,
+};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Handler = Arc<dyn Fn(CommandContext) -> BoxFuture + Send + Sync>;
+
+/// A requirement a player must meet to run a command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Requirement {
+    /// The player must have a role, by its name.
+    Role(String),
+    /// The player must have a permission, like `Bricks.ClearAll`.
+    Permission(String),
+    /// The player must meet any of the requirements.
+    Any(Vec<Requirement>),
+    /// The player must meet every requirement.
+    All(Vec<Requirement>),
+}
+
+impl Requirement {
+    /// Check whether a player, by their name, meets this requirement.
+    /// The host always meets every requirement.
+    pub async fn check(&self, omegga: &Omegga, player: &str) -> Result<bool, ResponseError> {
+        match omegga.get_player(player).await? {
+            Some(player) => self.check_player(omegga, &player).await,
+            None => Ok(false),
+        }
+    }
+
+    /// Check whether a player meets this requirement.
+    /// The host always meets every requirement.
+    pub async fn check_player(
+        &self,
+        omegga: &Omegga,
+        player: &Player,
+    ) -> Result<bool, ResponseError> {
+        if player.host == Some(true) {
+            return Ok(true);
+        }
+
+        let roles = if self.needs_roles() {
+            omegga
+                .get_player_roles(player.name.as_str())
+                .await?
+                .unwrap_or_default()
+        } else {
+            vec![]
+        };
+        let permissions = if self.needs_permissions() {
+            omegga
+                .get_player_permissions(player.name.as_str())
+                .await?
+                .unwrap_or_default()
+        } else {
+            Permissions::default()
+        };
+
+        Ok(self.is_met(&roles, &permissions))
+    }
+
+    /// Check this requirement against a player's roles and permissions.
+    pub fn is_met(&self, roles: &[String], permissions: &Permissions) -> bool {
+        match self {
+            Requirement::Role(role) => roles.iter().any(|r| r == role),
+            Requirement::Permission(permission) => permissions.has(permission),
+            Requirement::Any(reqs) => reqs.iter().any(|r| r.is_met(roles, permissions)),
+            Requirement::All(reqs) => reqs.iter().all(|r| r.is_met(roles, permissions)),
+        }
+    }
+
+    fn needs_roles(&self) -> bool {
+        match self {
+            Requirement::Role(_) => true,
+            Requirement::Permission(_) => false,
+            Requirement::Any(reqs) | Requirement::All(reqs) => {
+                reqs.iter().any(Requirement::needs_roles)
+            }
+        }
+    }
+
+    fn needs_permissions(&self) -> bool {
+        match self {
+            Requirement::Role(_) => false,
+            Requirement::Permission(_) => true,
+            Requirement::Any(reqs) | Requirement::All(reqs) => {
+                reqs.iter().any(Requirement::needs_permissions)
+            }
+        }
+    }
+}
+
+/// Formats a duration for chat, like `1m 30s` or `4.2s`.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0..=9 => format!("{:.1}s", duration.as_secs_f64()),
+        10..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m {}s", secs / 60, secs % 60),
+        _ => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+    }
+}
+
+/// Tracks command cooldowns, keyed by command name and player ID.
+///
+/// Cooldowns are stored as wall clock expiry times, so they can be persisted
+/// through the store with [`save`](Cooldowns::save) and [`load`](Cooldowns::load).
+#[derive(Debug, Default)]
+pub struct Cooldowns {
+    /// Expiry times in milliseconds since the Unix epoch, keyed by `command:player`.
+    expiries: DashMap<String, u64>,
+}
+
+impl Cooldowns {
+    /// The player ID used for global cooldowns.
+    pub const GLOBAL: &'static str = "*";
+
+    /// Create an empty set of cooldowns.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn now() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or_default()
+    }
+
+    fn key(command: &str, player: &str) -> String {
+        format!("{}:{}", command, player)
+    }
+
+    /// Get the time remaining on a cooldown for a player ID, or [`GLOBAL`](Cooldowns::GLOBAL).
+    pub fn remaining(&self, command: &str, player: &str) -> Option<Duration> {
+        let now = Self::now();
+        self.expiries
+            .get(&Self::key(command, player))
+            .map(|e| *e)
+            .filter(|&e| e > now)
+            .map(|e| Duration::from_millis(e - now))
+    }
+
+    /// Start a cooldown for a player ID, or [`GLOBAL`](Cooldowns::GLOBAL).
+    pub fn start(&self, command: &str, player: &str, duration: Duration) {
+        self.expiries.insert(
+            Self::key(command, player),
+            Self::now() + duration.as_millis() as u64,
+        );
+    }
+
+    /// Start several cooldowns for a command, each for a player ID or
+    /// [`GLOBAL`](Cooldowns::GLOBAL), unless any of them is still running.
+    ///
+    /// Each cooldown is checked and started atomically, so concurrent invocations can't
+    /// both pass. If one is running, none are started and the longest time remaining
+    /// is returned.
+    pub fn try_start(&self, command: &str, cooldowns: &[(&str, Duration)]) -> Result<(), Duration> {
+        let now = Self::now();
+        let mut started = vec![];
+        let mut running = None;
+        for (player, duration) in cooldowns {
+            let key = Self::key(command, player);
+            let expiry = now + duration.as_millis() as u64;
+            match self.expiries.entry(key.clone()) {
+                Entry::Occupied(e) if *e.get() > now => {
+                    running = Some(Duration::from_millis(*e.get() - now));
+                    break;
+                }
+                Entry::Occupied(mut e) => {
+                    e.insert(expiry);
+                }
+                Entry::Vacant(e) => {
+                    e.insert(expiry);
+                }
+            }
+            started.push((key, expiry));
+        }
+
+        let running = match running {
+            Some(running) => running,
+            None => return Ok(()),
+        };
+
+        // undo the cooldowns this started, unless something else has replaced them
+        for (key, expiry) in started {
+            self.expiries.remove_if(&key, |_, e| *e == expiry);
+        }
+        Err(cooldowns
+            .iter()
+            .filter_map(|(player, _)| self.remaining(command, player))
+            .fold(running, Duration::max))
+    }
+
+    /// Clear a cooldown for a player ID, or [`GLOBAL`](Cooldowns::GLOBAL).
+    pub fn clear(&self, command: &str, player: &str) {
+        self.expiries.remove(&Self::key(command, player));
+    }
+
+    /// Save unexpired cooldowns to the store under `key`.
+    pub fn save(&self, omegga: &Omegga, key: impl Into<String>) {
+        let now = Self::now();
+        self.expiries.retain(|_, e| *e > now);
+        let expiries = self
+            .expiries
+            .iter()
+            .map(|e| (e.key().clone(), *e.value()))
+            .collect::<HashMap<_, _>>();
+        omegga.store_set(key, serde_json::to_value(expiries).unwrap_or_default());
+    }
+
+    /// Load cooldowns from the store under `key`, merging them into this set.
+    pub async fn load(&self, omegga: &Omegga, key: impl Into<String>) -> Result<(), ResponseError> {
+        let expiries = omegga
+            .store_get(key)
+            .await?
+            .and_then(|v| serde_json::from_value::<HashMap<String, u64>>(v).ok())
+            .unwrap_or_default();
+        for (key, expiry) in expiries {
+            self.expiries
+                .entry(key)
+                .and_modify(|e| *e = (*e).max(expiry))
+                .or_insert(expiry);
+        }
+        Ok(())
+    }
+}
+
+/// The context a command handler is called with.
+#[derive(Clone)]
+pub struct CommandContext {
+    pub omegga: Omegga,
+    /// The name of the player that ran the command.
+    pub player: String,
+    /// The name of the command.
+    pub command: String,
+    pub args: Vec<String>,
+}
+
+impl CommandContext {
+    /// Whisper a line to the player that ran the command.
+    pub fn reply(&self, line: impl Into<String>) {
+        self.omegga.whisper(self.player.as_str(), line);
+    }
+}
+
+/// A command, with its handler and the requirements to run it.
+pub struct CommandSpec {
+    /// The command's name, description, example and arguments.
+    pub info: Command,
+    /// The requirement a player must meet to run the command, if any.
+    pub requirement: Option<Requirement>,
+    /// How long a player must wait between uses of the command.
+    pub cooldown: Option<Duration>,
+    /// How long anyone must wait after the command is used.
+    pub global_cooldown: Option<Duration>,
+    handler: Handler,
+}
+
+impl CommandSpec {
+    /// Create a new command with a handler.
+    pub fn new<F, Fut>(name: impl Into<String>, handler: F) -> Self
+    where
+        F: Fn(CommandContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        Self {
+            info: Command {
+                name: name.into(),
+                description: String::new(),
+                example: String::new(),
+                args: vec![],
+            },
+            requirement: None,
+            cooldown: None,
+            global_cooldown: None,
+            handler: Arc::new(move |ctx| Box::pin(handler(ctx))),
+        }
+    }
+
+    /// Set the command's description.
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.info.description = description.into();
+        self
+    }
+
+    /// Set the command's example.
+    pub fn example(mut self, example: impl Into<String>) -> Self {
+        self.info.example = example.into();
+        self
+    }
+
+    /// Add an argument to the command.
+    pub fn arg(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+        required: bool,
+    ) -> Self {
+        self.info.args.push(CommandArg {
+            name: name.into(),
+            description: description.into(),
+            required,
+        });
+        self
+    }
+
+    /// Require a player to meet a requirement to run the command.
+    pub fn require(mut self, requirement: Requirement) -> Self {
+        self.requirement = Some(requirement);
+        self
+    }
+
+    /// Require a player to have a role to run the command.
+    pub fn role(self, role: impl Into<String>) -> Self {
+        self.require(Requirement::Role(role.into()))
+    }
+
+    /// Require a player to have a permission to run the command.
+    pub fn permission(self, permission: impl Into<String>) -> Self {
+        self.require(Requirement::Permission(permission.into()))
+    }
+
+    /// Set how long a player must wait between uses of the command.
+    pub fn cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = Some(cooldown);
+        self
+    }
+
+    /// Set how long anyone must wait after the command is used.
+    pub fn global_cooldown(mut self, cooldown: Duration) -> Self {
+        self.global_cooldown = Some(cooldown);
+        self
+    }
+}
+
+impl CommandSpec {
+    /// Whether a player, if they're online, can run the command with their roles and
+    /// permissions. The host can run every command.
+    fn allowed_for(
+        &self,
+        player: Option<&Player>,
+        roles: &[String],
+        permissions: &Permissions,
+    ) -> bool {
+        match (&self.requirement, player) {
+            (None, _) => true,
+            (Some(_), None) => false,
+            (Some(_), Some(player)) if player.host == Some(true) => true,
+            (Some(r), Some(_)) => r.is_met(roles, permissions),
+        }
+    }
+
+    /// The command's usage, like `!clear <target> [quiet]`.
+    pub fn usage(&self) -> String {
+        let mut usage = format!("!{}", self.info.name);
+        for arg in &self.info.args {
+            if arg.required {
+                usage.push_str(&format!(" <{}>", arg.name));
+            } else {
+                usage.push_str(&format!(" [{}]", arg.name));
+            }
+        }
+        usage
+    }
+
+    /// Lines of help for the command, including its usage, description, arguments and example.
+    pub fn help(&self) -> Vec<RichText> {
+        let mut lines = vec![RichText::raw("<code>")
+            .push_text(self.usage())
+            .push_raw("</>")];
+        if !self.info.description.is_empty() {
+            lines.push(RichText::text(&self.info.description));
+        }
+        for arg in &self.info.args {
+            let mut line = RichText::raw("<code>").push_text(&arg.name).push_raw("</>");
+            if !arg.description.is_empty() {
+                line = line.push_text(format!(": {}", arg.description));
+            }
+            lines.push(line);
+        }
+        if !self.info.example.is_empty() {
+            lines.push(
+                RichText::text("Example: ")
+                    .push_raw("<code>")
+                    .push_text(&self.info.example)
+                    .push_raw("</>"),
+            );
+        }
+        lines
+    }
+}
+
+/// A set of commands, dispatched from [`Event::Command`] and [`Event::ChatCommand`] events.
+///
+/// Each command's requirement and cooldowns are checked before its handler runs.
+/// Players that don't meet the requirement are whispered
+/// [`denied_message`](Commands::denied_message) instead, and players that use a command
+/// too soon are whispered [`cooldown_message`](Commands::cooldown_message).
+/// Handlers are spawned as Tokio tasks, so they don't block the event loop.
+pub struct Commands {
+    commands: Vec<Arc<CommandSpec>>,
+    /// The message whispered to a player that doesn't meet a command's requirement.
+    pub denied_message: String,
+    /// The message whispered to a player that uses a command during its cooldown.
+    /// `{time}` is replaced with the time remaining.
+    pub cooldown_message: String,
+    /// The cooldowns for this set of commands.
+    pub cooldowns: Arc<Cooldowns>,
+    /// If set, cooldowns are saved to the store under this key whenever one starts.
+    /// Use [`Cooldowns::load`] on init to restore them.
+    pub cooldown_store_key: Option<String>,
+    /// The name of the built-in help command, or `None` to disable it.
+    ///
+    /// `!help` lists the commands the player can use, and `!help <command>`
+    /// shows a command's usage.
+    pub help_command: Option<String>,
+}
+
+impl Commands {
+    /// Create an empty set of commands.
+    pub fn new() -> Self {
+        Self {
+            commands: vec![],
+            denied_message: String::from("You don't have permission to use that command."),
+            cooldown_message: String::from("You must wait {time} before using that command again."),
+            cooldowns: Arc::new(Cooldowns::new()),
+            cooldown_store_key: None,
+            help_command: Some(String::from("help")),
+        }
+    }
+
+    /// Add a command, replacing any existing command with the same name.
+    pub fn add(&mut self, command: CommandSpec) {
+        self.commands.retain(|c| c.info.name != command.info.name);
+        self.commands.push(Arc::new(command));
+    }
+
+    /// Get a command by its name.
+    pub fn get(&self, name: &str) -> Option<&CommandSpec> {
+        self.commands
+            .iter()
+            .find(|c| c.info.name == name)
+            .map(Arc::as_ref)
+    }
+
+    /// Iterate over the commands, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &CommandSpec> {
+        self.commands.iter().map(Arc::as_ref)
+    }
+
+    /// The names of the commands, in the order they were added, including the help command.
+    pub fn names(&self) -> Vec<&str> {
+        let mut names = self
+            .commands
+            .iter()
+            .map(|c| c.info.name.as_str())
+            .collect::<Vec<_>>();
+        if let Some(help) = &self.help_command {
+            if !names.contains(&help.as_str()) {
+                names.push(help);
+            }
+        }
+        names
+    }
+
+    /// Register the commands with Omegga. Call when the plugin is initialized.
+    pub fn register(&self, omegga: &Omegga, id: rpc::RequestId) {
+        omegga.register_commands(id, &self.names());
+    }
+
+    /// Handle an event, returning `true` if it was a command in this set.
+    pub fn handle(&self, omegga: &Omegga, event: &Event) -> bool {
+        let (player, command, args) = match event {
+            Event::Command {
+                player,
+                command,
+                args,
+            }
+            | Event::ChatCommand {
+                player,
+                command,
+                args,
+            } => (player, command, args),
+            _ => return false,
+        };
+
+        let spec = match self.commands.iter().find(|c| &c.info.name == command) {
+            Some(spec) => Arc::clone(spec),
+            None if self.help_command.as_ref() == Some(command) => {
+                self.spawn_help(omegga, player.clone(), args.first().cloned());
+                return true;
+            }
+            None => return false,
+        };
+
+        let ctx = CommandContext {
+            omegga: omegga.clone(),
+            player: player.clone(),
+            command: command.clone(),
+            args: args.clone(),
+        };
+        let denied_message = self.denied_message.clone();
+        let cooldown_message = self.cooldown_message.clone();
+        let cooldowns = Arc::clone(&self.cooldowns);
+        let cooldown_store_key = self.cooldown_store_key.clone();
+
+        omegga.spawn_task(async move {
+            let info = if spec.requirement.is_some() || spec.cooldown.is_some() {
+                match ctx.omegga.get_player(ctx.player.as_str()).await {
+                    Ok(info) => info,
+                    Err(_) => return,
+                }
+            } else {
+                None
+            };
+
+            if let Some(requirement) = &spec.requirement {
+                let allowed = match &info {
+                    Some(info) => requirement.check_player(&ctx.omegga, info).await,
+                    None => Ok(false),
+                };
+                match allowed {
+                    Ok(true) => (),
+                    Ok(false) => return ctx.reply(denied_message),
+                    Err(_) => return,
+                }
+            }
+
+            let name = spec.info.name.as_str();
+            let id = info.as_ref().map_or(ctx.player.as_str(), |p| p.id.as_str());
+            let cooldowns_for = [
+                (Cooldowns::GLOBAL, spec.global_cooldown),
+                (id, spec.cooldown),
+            ];
+
+            let cooldowns_for = cooldowns_for
+                .iter()
+                .filter_map(|&(id, cooldown)| Some((id, cooldown?)))
+                .collect::<Vec<_>>();
+            if let Err(remaining) = cooldowns.try_start(name, &cooldowns_for) {
+                let time = format_duration(remaining);
+                return ctx.reply(cooldown_message.replace("{time}", &time));
+            }
+            if let (false, Some(key)) = (cooldowns_for.is_empty(), cooldown_store_key) {
+                cooldowns.save(&ctx.omegga, key);
+            }
+
+            (spec.handler)(ctx).await;
+        });
+
+        true
+    }
+
+    fn spawn_help(&self, omegga: &Omegga, player: String, command: Option<String>) {
+        let commands = self.commands.clone();
+        let task_omegga = omegga.clone();
+        omegga.spawn_task(async move {
+            let omegga = task_omegga;
+            let info = match omegga.get_player(player.as_str()).await {
+                Ok(info) => info,
+                Err(_) => return,
+            };
+
+            // only fetch roles and permissions if a command needs them
+            let host = info.as_ref().and_then(|p| p.host) == Some(true);
+            let needs = |f: fn(&Requirement) -> bool| {
+                !host
+                    && commands
+                        .iter()
+                        .any(|c| c.requirement.as_ref().is_some_and(f))
+            };
+            let roles = if needs(Requirement::needs_roles) {
+                omegga.get_player_roles(player.as_str()).await
+            } else {
+                Ok(None)
+            };
+            let permissions = if needs(Requirement::needs_permissions) {
+                omegga.get_player_permissions(player.as_str()).await
+            } else {
+                Ok(None)
+            };
+            let (roles, permissions) = match (roles, permissions) {
+                (Ok(r), Ok(p)) => (r.unwrap_or_default(), p.unwrap_or_default()),
+                _ => return,
+            };
+
+            let allowed = commands
+                .iter()
+                .filter(|c| c.allowed_for(info.as_ref(), &roles, &permissions))
+                .collect::<Vec<_>>();
+
+            match command {
+                Some(command) => {
+                    let command = command.trim_start_matches('!');
+                    match allowed.iter().find(|c| c.info.name == command) {
+                        Some(spec) => {
+                            for line in spec.help() {
+                                omegga.whisper_long(player.as_str(), line);
+                            }
+                        }
+                        None => omegga.whisper(
+                            player.as_str(),
+                            RichText::text(format!("No command named {}.", command)),
+                        ),
+                    }
+                }
+                None => {
+                    omegga.whisper(player.as_str(), "<b>Commands</>");
+                    for spec in allowed {
+                        let mut line = RichText::raw("<code>")
+                            .push_text(format!("!{}", spec.info.name))
+                            .push_raw("</>");
+                        if !spec.info.description.is_empty() {
+                            line = line.push_text(format!(": {}", spec.info.description));
+                        }
+                        omegga.whisper_long(player.as_str(), line);
+                    }
+                }
+            }
+        });
+    }
+}
+
+impl Default for Commands {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chat;
+
+    const MINUTE: Duration = Duration::from_secs(60);
+
+    fn role(name: &str) -> Requirement {
+        Requirement::Role(name.to_string())
+    }
+
+    fn permission(name: &str) -> Requirement {
+        Requirement::Permission(name.to_string())
+    }
+
+    fn roles(names: &[&str]) -> Vec<String> {
+        names.iter().map(|r| r.to_string()).collect()
+    }
+
+    fn permissions(allowed: &[(&str, bool)]) -> Permissions {
+        Permissions(allowed.iter().map(|&(k, v)| (k.to_string(), v)).collect())
+    }
+
+    fn player(host: Option<bool>) -> Player {
+        Player {
+            name: "x".into(),
+            id: "x-id".into(),
+            controller: "x-controller".into(),
+            state: "x-state".into(),
+            host,
+        }
+    }
+
+    #[test]
+    fn checks_roles() {
+        let none = Permissions::default();
+        assert!(role("Admin").is_met(&roles(&["Member", "Admin"]), &none));
+        assert!(!role("Admin").is_met(&roles(&["Member"]), &none));
+        assert!(!role("Admin").is_met(&roles(&["admin"]), &none));
+        assert!(!role("Admin").is_met(&[], &none));
+    }
+
+    #[test]
+    fn checks_permissions() {
+        let perms = permissions(&[("Bricks.ClearAll", true), ("Players.Kick", false)]);
+        assert!(permission("Bricks.ClearAll").is_met(&[], &perms));
+        // denied and missing permissions are both unmet
+        assert!(!permission("Players.Kick").is_met(&[], &perms));
+        assert!(!permission("Players.Ban").is_met(&[], &perms));
+    }
+
+    #[test]
+    fn checks_any_and_all() {
+        let perms = permissions(&[("Players.Kick", true)]);
+        let any = Requirement::Any(vec![role("Admin"), role("Moderator")]);
+        assert!(any.is_met(&roles(&["Moderator"]), &perms));
+        assert!(!any.is_met(&roles(&["Member"]), &perms));
+
+        let all = Requirement::All(vec![role("Moderator"), permission("Players.Kick")]);
+        assert!(all.is_met(&roles(&["Moderator"]), &perms));
+        assert!(!all.is_met(&roles(&["Member"]), &perms));
+        assert!(!all.is_met(&roles(&["Moderator"]), &Permissions::default()));
+
+        let nested = Requirement::Any(vec![
+            role("Admin"),
+            Requirement::All(vec![role("Moderator"), role("Trusted")]),
+        ]);
+        assert!(nested.is_met(&roles(&["Admin"]), &perms));
+        assert!(nested.is_met(&roles(&["Trusted", "Moderator"]), &perms));
+        assert!(!nested.is_met(&roles(&["Moderator"]), &perms));
+
+        assert!(!Requirement::Any(vec![]).is_met(&[], &perms));
+        assert!(Requirement::All(vec![]).is_met(&[], &perms));
+    }
+
+    #[test]
+    fn knows_what_to_fetch() {
+        let both = Requirement::All(vec![role("Admin"), permission("Players.Kick")]);
+        assert!(both.needs_roles() && both.needs_permissions());
+        assert!(role("Admin").needs_roles() && !role("Admin").needs_permissions());
+        let any = Requirement::Any(vec![permission("Players.Kick")]);
+        assert!(!any.needs_roles() && any.needs_permissions());
+    }
+
+    #[tokio::test]
+    async fn lets_the_host_through() {
+        // the host is allowed without fetching their roles or permissions
+        let omegga = Omegga::new();
+        let requirement = Requirement::All(vec![role("Admin"), permission("Players.Kick")]);
+        assert!(requirement
+            .check_player(&omegga, &player(Some(true)))
+            .await
+            .unwrap());
+        assert!(omegga.awaiter_txs.is_empty());
+    }
+
+    #[test]
+    fn shows_usage() {
+        let spec = CommandSpec::new("kick", |_| async {})
+            .arg("target", "the player to kick", true)
+            .arg("reason", "", false)
+            .role("Moderator");
+        assert_eq!(spec.usage(), "!kick <target> [reason]");
+        assert_eq!(spec.requirement, Some(role("Moderator")));
+        assert_eq!(CommandSpec::new("ping", |_| async {}).usage(), "!ping");
+    }
+
+    #[test]
+    fn filters_help_by_requirement() {
+        let spec = |name: &str| CommandSpec::new(name, |_| async {});
+        let commands = [
+            spec("ping"),
+            spec("kick").role("Moderator"),
+            spec("clear").permission("Bricks.ClearAll"),
+        ];
+        let allowed = |player: Option<&Player>, roles: &[String], permissions: &Permissions| {
+            commands
+                .iter()
+                .filter(|c| c.allowed_for(player, roles, permissions))
+                .map(|c| c.info.name.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        let member = player(None);
+        let none = Permissions::default();
+        assert_eq!(allowed(Some(&member), &[], &none), ["ping"]);
+        assert_eq!(
+            allowed(Some(&member), &roles(&["Moderator"]), &none),
+            ["ping", "kick"]
+        );
+        assert_eq!(
+            allowed(
+                Some(&member),
+                &[],
+                &permissions(&[("Bricks.ClearAll", true)])
+            ),
+            ["ping", "clear"]
+        );
+        assert_eq!(
+            allowed(Some(&player(Some(true))), &[], &none),
+            ["ping", "kick", "clear"]
+        );
+        // players that can't be found only see commands without requirements
+        assert_eq!(allowed(None, &roles(&["Moderator"]), &none), ["ping"]);
+    }
+
+    #[test]
+    fn shows_help() {
+        let spec = CommandSpec::new("kick", |_| async {})
+            .description("Kick a <b>player</>")
+            .arg("target", "the player to kick", true)
+            .arg("reason", "", false)
+            .example("!kick x");
+        let help = spec
+            .help()
+            .into_iter()
+            .map(|line| line.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            help,
+            [
+                format!("<code>{}</>", chat::escape("!kick <target> [reason]")),
+                format!(
+                    "Kick a {}player{}",
+                    chat::escape("<b>"),
+                    chat::escape("</>")
+                ),
+                "<code>target</>: the player to kick".to_string(),
+                "<code>reason</>".to_string(),
+                "Example: <code>!kick x</>".to_string(),
+            ]
+        );
+
+        let bare = CommandSpec::new("ping", |_| async {}).help();
+        assert_eq!(bare.len(), 1);
+    }
+
+    #[test]
+    fn formats_durations() {
+        assert_eq!(format_duration(Duration::ZERO), "0.0s");
+        assert_eq!(format_duration(Duration::from_millis(4250)), "4.2s");
+        assert_eq!(format_duration(Duration::from_millis(9990)), "10.0s");
+        assert_eq!(format_duration(Duration::from_secs(10)), "10s");
+        assert_eq!(format_duration(Duration::from_secs(59)), "59s");
+        assert_eq!(format_duration(MINUTE), "1m 0s");
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m 30s");
+        assert_eq!(format_duration(Duration::from_secs(3599)), "59m 59s");
+        assert_eq!(format_duration(Duration::from_secs(3600)), "1h 0m");
+        assert_eq!(
+            format_duration(Duration::from_secs(2 * 3600 + 5 * 60 + 59)),
+            "2h 5m"
+        );
+    }
+
+    #[test]
+    fn starts_cooldowns() {
+        let cooldowns = Cooldowns::new();
+        let both = [(Cooldowns::GLOBAL, MINUTE), ("p", 2 * MINUTE)];
+        assert_eq!(cooldowns.try_start("cmd", &both), Ok(()));
+        assert!(cooldowns.remaining("cmd", Cooldowns::GLOBAL).is_some());
+        assert!(cooldowns.remaining("cmd", "p").is_some());
+
+        // the longest remaining cooldown is reported
+        let remaining = cooldowns.try_start("cmd", &both).unwrap_err();
+        assert!(remaining > MINUTE && remaining <= 2 * MINUTE);
+
+        // other commands are unaffected
+        assert_eq!(cooldowns.try_start("other", &both), Ok(()));
+        assert_eq!(cooldowns.try_start("cmd", &[]), Ok(()));
+    }
+
+    #[test]
+    fn starts_nothing_while_running() {
+        let cooldowns = Cooldowns::new();
+        cooldowns.start("cmd", "p", MINUTE);
+
+        let both = [(Cooldowns::GLOBAL, MINUTE), ("p", MINUTE)];
+        assert!(cooldowns.try_start("cmd", &both).is_err());
+        assert_eq!(cooldowns.remaining("cmd", Cooldowns::GLOBAL), None);
+
+        cooldowns.clear("cmd", "p");
+        assert_eq!(cooldowns.try_start("cmd", &both), Ok(()));
+    }
+
+    #[test]
+    fn lets_one_concurrent_invocation_through() {
+        let cooldowns = Arc::new(Cooldowns::new());
+        let barrier = Arc::new(std::sync::Barrier::new(16));
+        let threads = (0..16)
+            .map(|_| {
+                let cooldowns = Arc::clone(&cooldowns);
+                let barrier = Arc::clone(&barrier);
+                std::thread::spawn(move || {
+                    barrier.wait();
+                    cooldowns
+                        .try_start("cmd", &[(Cooldowns::GLOBAL, MINUTE), ("p", MINUTE)])
+                        .is_ok()
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let passed = threads
+            .into_iter()
+            .map(|t| t.join().unwrap())
+            .filter(|&ok| ok)
+            .count();
+        assert_eq!(passed, 1);
+    }
+}
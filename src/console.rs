@@ -0,0 +1,494 @@
+use std::{collections::HashMap, sync::Mutex, time::Duration};
+
+use regex::{Captures, Regex};
+use thiserror::Error;
+#[cfg(feature = "tokio")]
+use tokio::sync;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{events::Event, geometry::Vec3};
+
+/// A typed event parsed from a server console line.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LogEvent {
+    /// A map finished loading.
+    MapLoaded { map: String },
+    /// A line matched a custom pattern, with its named captures.
+    Custom {
+        name: String,
+        captures: HashMap<String, String>,
+    },
+}
+
+type Parser = fn(&Captures) -> Option<LogEvent>;
+
+fn capture(caps: &Captures, name: &str) -> Option<String> {
+    caps.name(name).map(|m| m.as_str().to_string())
+}
+
+/// The built-in patterns, by name, with the parser for each.
+const BUILTINS: &[(&str, &str, Parser)] = &[(
+    "map_loaded",
+    r"^LogLoad: Took [\d.]+ seconds to LoadMap\(/Game/Maps/(?P<map>[^/]+)/[^)]*\)$",
+    |c| {
+        Some(LogEvent::MapLoaded {
+            map: capture(c, "map")?,
+        })
+    },
+)];
+
+/// Strip the `[timestamp][frame]` prefix from a console line, if it has one.
+pub fn strip_prefix(line: &str) -> &str {
+    let mut rest = line;
+    for _ in 0..2 {
+        match rest.strip_prefix('[').and_then(|r| r.split_once(']')) {
+            Some((_, r)) => rest = r,
+            None => return line,
+        }
+    }
+    rest
+}
+
+struct Matcher {
+    name: String,
+    regex: Regex,
+    /// The built-in parser, or `None` for a custom pattern.
+    parser: Option<Parser>,
+}
+
+/// Matches console lines against a list of patterns, turning them into [`LogEvent`]s.
+///
+/// [`LineMatcher::new`] starts with a built-in pattern for the engine's `map_loaded` line.
+/// Anything else, like kills or kicks, can be matched by adding a pattern for the lines
+/// the server prints. Log formats change between game versions, so a built-in pattern can
+/// be replaced with [`add`](LineMatcher::add), keeping its named captures.
+pub struct LineMatcher {
+    matchers: Vec<Matcher>,
+}
+
+impl Default for LineMatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl LineMatcher {
+    /// Create a line matcher with the built-in patterns.
+    pub fn new() -> Self {
+        Self {
+            matchers: BUILTINS
+                .iter()
+                .map(|(name, pattern, parser)| Matcher {
+                    name: name.to_string(),
+                    regex: Regex::new(pattern).expect("built-in patterns are valid"),
+                    parser: Some(*parser),
+                })
+                .collect(),
+        }
+    }
+
+    /// Create a line matcher with no patterns.
+    pub fn empty() -> Self {
+        Self { matchers: vec![] }
+    }
+
+    /// Add a pattern, replacing any pattern with the same name. Lines are matched without
+    /// their `[timestamp][frame]` prefix.
+    ///
+    /// Lines matching a new pattern produce [`LogEvent::Custom`] with the pattern's named
+    /// captures. Replacing a built-in pattern keeps its event type.
+    pub fn add(&mut self, name: impl Into<String>, pattern: &str) -> Result<(), regex::Error> {
+        let name = name.into();
+        let regex = Regex::new(pattern)?;
+        match self.matchers.iter_mut().find(|m| m.name == name) {
+            Some(matcher) => matcher.regex = regex,
+            None => self.matchers.push(Matcher {
+                name,
+                regex,
+                parser: None,
+            }),
+        }
+        Ok(())
+    }
+
+    /// Remove a pattern by its name, returning whether it existed.
+    pub fn remove(&mut self, name: &str) -> bool {
+        let len = self.matchers.len();
+        self.matchers.retain(|m| m.name != name);
+        self.matchers.len() != len
+    }
+
+    /// The names of every pattern, in the order they're tried.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.matchers.iter().map(|m| m.name.as_str())
+    }
+
+    /// Parse a console line, returning the event for the first pattern it matches.
+    pub fn parse(&self, line: &str) -> Option<LogEvent> {
+        let line = strip_prefix(line);
+        self.matchers.iter().find_map(|m| {
+            let caps = m.regex.captures(line)?;
+            match m.parser {
+                Some(parser) => parser(&caps),
+                None => Some(LogEvent::Custom {
+                    name: m.name.clone(),
+                    captures: m
+                        .regex
+                        .capture_names()
+                        .flatten()
+                        .filter_map(|n| Some((n.to_string(), capture(&caps, n)?)))
+                        .collect(),
+                }),
+            }
+        })
+    }
+
+    /// Parse an [`Event::Line`], returning `None` for any other event.
+    pub fn parse_event(&self, event: &Event) -> Option<LogEvent> {
+        match event {
+            Event::Line(line) => self.parse(line),
+            _ => None,
+        }
+    }
+}
+
+/// Which console lines a capture collects.
+/// Lines are matched without their `[timestamp][frame]` prefix.
+#[cfg_attr(
+    feature = "tokio",
+    doc = "See [`Omegga::exec_and_capture`](crate::Omegga::exec_and_capture)."
+)]
+#[derive(Debug, Clone)]
+pub enum Capture {
+    /// Collect every line matching a pattern until the timeout.
+    Matching(Regex),
+    /// Collect every line until one matches a terminator, which is included.
+    Until(Regex),
+}
+
+impl Capture {
+    /// Collect every line matching a pattern until the timeout.
+    pub fn matching(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Capture::Matching)
+    }
+
+    /// Collect every line until one matches a terminator pattern.
+    pub fn until(pattern: &str) -> Result<Self, regex::Error> {
+        Regex::new(pattern).map(Capture::Until)
+    }
+}
+
+/// A [`Capture::Until`] timed out before its terminator line.
+#[derive(Error, Debug, Clone)]
+#[error("timed out waiting for console output")]
+pub struct CaptureTimeout {
+    /// The lines collected before the timeout.
+    pub lines: Vec<String>,
+}
+
+/// Forwards console lines to the running capture, if there is one.
+#[derive(Default)]
+pub(crate) struct LineCapture {
+    /// Held for the length of a capture, so captures don't steal each other's output.
+    #[cfg(feature = "tokio")]
+    pub(crate) lock: sync::Mutex<()>,
+    pub(crate) sink: Mutex<Option<UnboundedSender<String>>>,
+}
+
+impl LineCapture {
+    pub(crate) fn forward(&self, line: &str) {
+        if let Some(sink) = self.sink.lock().unwrap().as_ref() {
+            let _ = sink.send(line.to_string());
+        }
+    }
+}
+
+/// A console command argument that can't be sent safely.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid console command argument: {0:?}")]
+pub struct InvalidArgument(pub String);
+
+/// Quote a console command argument.
+///
+/// The console can't escape double quotes and treats line breaks as the end of a command,
+/// so arguments containing either, or any other control character, are rejected.
+pub fn quote(arg: &str) -> Result<String, InvalidArgument> {
+    if arg.contains(|c: char| c == '"' || c.is_control()) {
+        return Err(InvalidArgument(arg.to_string()));
+    }
+    Ok(format!("\"{}\"", arg))
+}
+
+/// A Brickadia server console command, with its arguments quoted.
+///
+/// Run one with [`Omegga::exec_command`](crate::Omegga::exec_command), or get the line
+/// sent to the server with [`line`](ConsoleCommand::line).
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConsoleCommand {
+    /// Kick a player, by their name.
+    Kick { player: String, reason: String },
+    /// Ban a player, by their name, for a duration, or forever if `None`.
+    /// Durations are rounded up to whole minutes, and are at least one minute.
+    Ban {
+        player: String,
+        duration: Option<Duration>,
+        reason: String,
+    },
+    /// Kill a player, by their name.
+    Kill { player: String },
+    /// Teleport a player, by their name.
+    Teleport { player: String, position: Vec3 },
+    /// Move a player, by their name, to a team in their minigame, by its index.
+    SetTeam { player: String, team: u32 },
+    /// Run a chat command as the server, like `/ClearAll`.
+    ChatCommand { command: String, args: Vec<String> },
+}
+
+impl ConsoleCommand {
+    /// Kick a player, by their name.
+    pub fn kick(player: impl Into<String>, reason: impl Into<String>) -> Self {
+        ConsoleCommand::Kick {
+            player: player.into(),
+            reason: reason.into(),
+        }
+    }
+
+    /// Ban a player, by their name, for a duration, or forever if `None`.
+    pub fn ban(
+        player: impl Into<String>,
+        duration: Option<Duration>,
+        reason: impl Into<String>,
+    ) -> Self {
+        ConsoleCommand::Ban {
+            player: player.into(),
+            duration,
+            reason: reason.into(),
+        }
+    }
+
+    /// Kill a player, by their name.
+    pub fn kill(player: impl Into<String>) -> Self {
+        ConsoleCommand::Kill {
+            player: player.into(),
+        }
+    }
+
+    /// Teleport a player, by their name.
+    pub fn teleport(player: impl Into<String>, position: impl Into<Vec3>) -> Self {
+        ConsoleCommand::Teleport {
+            player: player.into(),
+            position: position.into(),
+        }
+    }
+
+    /// Move a player, by their name, to a team in their minigame, by its index.
+    pub fn set_team(player: impl Into<String>, team: u32) -> Self {
+        ConsoleCommand::SetTeam {
+            player: player.into(),
+            team,
+        }
+    }
+
+    /// Run a chat command as the server. The command's name is given without its `/`.
+    pub fn chat_command<I, S>(command: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        ConsoleCommand::ChatCommand {
+            command: command.into(),
+            args: args.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Get the line sent to the server for this command.
+    ///
+    /// This fails if an argument contains a double quote or control character, rather than
+    /// changing it, so a command never targets a different player than intended.
+    pub fn line(&self) -> Result<String, InvalidArgument> {
+        Ok(match self {
+            ConsoleCommand::Kick { player, reason } => {
+                format!("Chat.Command /Kick {} {}", quote(player)?, quote(reason)?)
+            }
+            ConsoleCommand::Ban {
+                player,
+                duration,
+                reason,
+            } => {
+                let minutes = duration.map_or(-1, |d| d.as_millis().div_ceil(60_000).max(1) as i64);
+                format!(
+                    "Chat.Command /Ban {} {} {}",
+                    quote(player)?,
+                    minutes,
+                    quote(reason)?
+                )
+            }
+            ConsoleCommand::Kill { player } => format!("Server.Players.Kill {}", quote(player)?),
+            ConsoleCommand::Teleport { player, position } => format!(
+                "Chat.Command /TP {} {} {} {} 0",
+                quote(player)?,
+                position.x,
+                position.y,
+                position.z
+            ),
+            ConsoleCommand::SetTeam { player, team } => {
+                format!("Server.Players.SetTeam {} {}", quote(player)?, team)
+            }
+            ConsoleCommand::ChatCommand { command, args } => {
+                // command names can't be quoted, so only allow characters that can't break out
+                if command.is_empty()
+                    || !command
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+                {
+                    return Err(InvalidArgument(command.clone()));
+                }
+                let mut line = format!("Chat.Command /{}", command);
+                for arg in args {
+                    line.push(' ');
+                    line.push_str(&quote(arg)?);
+                }
+                line
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lines as the server prints them, with their `[timestamp][frame]` prefix.
+    const MAP_LOADED: &str =
+        "[2021.09.24-21.13.45:123][  0]LogLoad: Took 0.254771 seconds to LoadMap(/Game/Maps/Plate/Plate)";
+    const CHAT: &str = "[2021.09.24-21.14.02:456][912]LogChat: x: hello there";
+
+    #[test]
+    fn strips_prefix() {
+        assert_eq!(strip_prefix(CHAT), "LogChat: x: hello there");
+        assert_eq!(strip_prefix("LogChat: x: hi"), "LogChat: x: hi");
+        assert_eq!(strip_prefix("[only one]LogChat"), "[only one]LogChat");
+    }
+
+    #[test]
+    fn matches_builtins() {
+        let matcher = LineMatcher::new();
+        assert_eq!(matcher.names().collect::<Vec<_>>(), ["map_loaded"]);
+        assert_eq!(
+            matcher.parse(MAP_LOADED),
+            Some(LogEvent::MapLoaded {
+                map: "Plate".to_string()
+            })
+        );
+        assert_eq!(
+            matcher.parse_event(&Event::Line(MAP_LOADED.to_string())),
+            matcher.parse(MAP_LOADED)
+        );
+        assert_eq!(matcher.parse(CHAT), None);
+        assert_eq!(LineMatcher::empty().parse(MAP_LOADED), None);
+    }
+
+    #[test]
+    fn matches_custom_patterns() {
+        let mut matcher = LineMatcher::new();
+        matcher
+            .add("chat", r"^LogChat: (?P<name>.+?): (?P<message>.*)$")
+            .unwrap();
+        match matcher.parse(CHAT) {
+            Some(LogEvent::Custom { name, captures }) => {
+                assert_eq!(name, "chat");
+                assert_eq!(captures["name"], "x");
+                assert_eq!(captures["message"], "hello there");
+            }
+            e => panic!("expected a custom event, got {:?}", e),
+        }
+
+        assert!(matcher.remove("chat"));
+        assert!(!matcher.remove("chat"));
+        assert_eq!(matcher.parse(CHAT), None);
+        assert!(matcher.add("bad", "(").is_err());
+    }
+
+    #[test]
+    fn replaces_builtin_patterns() {
+        // a made-up format, standing in for a changed log line
+        let mut matcher = LineMatcher::new();
+        matcher
+            .add("map_loaded", r"^Loaded map (?P<map>\w+)$")
+            .unwrap();
+        assert_eq!(matcher.names().count(), 1);
+        assert_eq!(matcher.parse(MAP_LOADED), None);
+        assert_eq!(
+            matcher.parse("[2021.09.24-21.13.45:123][  0]Loaded map Studio"),
+            Some(LogEvent::MapLoaded {
+                map: "Studio".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn quotes_arguments() {
+        assert_eq!(quote("Admin").unwrap(), "\"Admin\"");
+        assert_eq!(quote("a 'b' c").unwrap(), "\"a 'b' c\"");
+        assert!(quote("Admin\"").is_err());
+        assert!(quote("x\nServer.Players.Kill \"y\"").is_err());
+        assert!(quote("x\r").is_err());
+    }
+
+    #[test]
+    fn rejects_crafted_names() {
+        // stripping the quote would kick `Admin` instead
+        assert_eq!(
+            ConsoleCommand::kick("Admin\"", "bye").line(),
+            Err(InvalidArgument("Admin\"".to_string()))
+        );
+        assert!(ConsoleCommand::ban("Admin\"", None, "").line().is_err());
+        assert!(ConsoleCommand::kill("x\n").line().is_err());
+        assert!(ConsoleCommand::set_team("\"", 1).line().is_err());
+        assert!(ConsoleCommand::chat_command("ClearBricks", ["\""])
+            .line()
+            .is_err());
+        assert!(
+            ConsoleCommand::chat_command("Kick \"x\"", Vec::<String>::new())
+                .line()
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn formats_commands() {
+        assert_eq!(
+            ConsoleCommand::kick("x", "spam").line().unwrap(),
+            "Chat.Command /Kick \"x\" \"spam\""
+        );
+        assert_eq!(
+            ConsoleCommand::kill("x").line().unwrap(),
+            "Server.Players.Kill \"x\""
+        );
+        assert_eq!(
+            ConsoleCommand::chat_command("ClearBricks", ["x"])
+                .line()
+                .unwrap(),
+            "Chat.Command /ClearBricks \"x\""
+        );
+    }
+
+    #[test]
+    fn rounds_ban_durations_up() {
+        let ban = |duration| ConsoleCommand::ban("x", duration, "r").line().unwrap();
+        assert_eq!(ban(None), "Chat.Command /Ban \"x\" -1 \"r\"");
+        assert_eq!(ban(Some(Duration::ZERO)), "Chat.Command /Ban \"x\" 1 \"r\"");
+        assert_eq!(
+            ban(Some(Duration::from_secs(30))),
+            "Chat.Command /Ban \"x\" 1 \"r\""
+        );
+        assert_eq!(
+            ban(Some(Duration::from_secs(60))),
+            "Chat.Command /Ban \"x\" 1 \"r\""
+        );
+        assert_eq!(
+            ban(Some(Duration::from_secs(61))),
+            "Chat.Command /Ban \"x\" 2 \"r\""
+        );
+    }
+}
@@ -1,6 +1,11 @@
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::{resources::Player, rpc::RequestId};
+use crate::{
+    geometry::Vec3,
+    resources::{Host, Player},
+    rpc::RequestId,
+};
 
 #[derive(Debug, Clone)]
 pub enum Event {
@@ -11,9 +16,7 @@ pub enum Event {
     Stop {
         id: RequestId,
     },
-    Bootstrap {
-        omegga: Value,
-    },
+    Bootstrap(Bootstrap),
     PluginPlayersRaw {
         players: Vec<Player>,
     },
@@ -31,7 +34,7 @@ pub enum Event {
         name: String,
         id: String,
     },
-    Version(Value),
+    Version(VersionInfo),
     Unauthorized,
     Join(Player),
     Leave(Player),
@@ -56,7 +59,82 @@ pub enum Event {
         player: Player,
         args: Vec<String>,
     },
-    Autorestart(Value),
+    Autorestart(AutorestartNotice),
+}
+
+/// The data Omegga sends a plugin when it starts.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Bootstrap {
+    pub host: Option<Host>,
+    pub map: Option<String>,
+    /// The game version.
+    #[serde(default, deserialize_with = "changelist")]
+    pub version: Option<u32>,
+    #[serde(default)]
+    pub verbose: bool,
+    /// The path to the server's directory.
+    pub path: Option<String>,
+    /// The path to the server's saves.
+    #[serde(rename = "savePath")]
+    pub save_path: Option<String>,
+    /// The path to the server's config.
+    #[serde(rename = "configPath")]
+    pub config_path: Option<String>,
+    /// The players online when the plugin started.
+    #[serde(default, alias = "online")]
+    pub players: Vec<Player>,
+    /// Omegga's config.
+    #[serde(default)]
+    pub config: Value,
+}
+
+/// The game version the server is running.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VersionInfo {
+    /// The version as Omegga sent it.
+    pub raw: String,
+    /// The game's changelist number, like `7870`.
+    pub changelist: Option<u32>,
+}
+
+impl From<Value> for VersionInfo {
+    fn from(value: Value) -> Self {
+        let raw = match value {
+            Value::String(s) => s,
+            Value::Null => String::new(),
+            v => v.to_string(),
+        };
+        let changelist = parse_changelist(&raw);
+        Self { raw, changelist }
+    }
+}
+
+/// Parse a changelist number from a version like `7870` or `CL7870`.
+fn parse_changelist(version: &str) -> Option<u32> {
+    version
+        .trim()
+        .trim_start_matches(|c: char| !c.is_ascii_digit())
+        .split(|c: char| !c.is_ascii_digit())
+        .next()?
+        .parse()
+        .ok()
+}
+
+fn changelist<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<Option<u32>, D::Error> {
+    Ok(VersionInfo::from(Value::deserialize(deserializer)?).changelist)
+}
+
+/// A notice that the server is about to automatically restart, with what it will restore.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutorestartNotice {
+    #[serde(default)]
+    pub players: bool,
+    #[serde(default)]
+    pub bricks: bool,
+    #[serde(default)]
+    pub minigames: bool,
+    #[serde(default)]
+    pub environment: bool,
 }
 
 /// A player from interact.
@@ -73,9 +151,75 @@ pub struct BrickInteraction {
     pub brick_name: String,
     pub brick_size: Vec<u64>,
     pub player: PlayerInteract,
-    pub position: (f64, f64, f64),
+    pub position: Vec3,
     pub data: Option<Value>,
     pub error: bool,
     pub json: bool,
     pub message: String
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A bootstrap payload as Omegga sends it, with players as `raw()` arrays.
+    const BOOTSTRAP: &str = r#"{
+        "host": { "name": "x", "id": "a1b2c3d4-0000-0000-0000-000000000000" },
+        "version": 7870,
+        "verbose": false,
+        "online": [
+            ["x", "a1b2c3d4-0000-0000-0000-000000000000", "BP_PlayerController_C_2147482439", "BP_PlayerState_C_2147482424"],
+            ["y", "e5f6a7b8-0000-0000-0000-000000000000", "BP_PlayerController_C_2147482210", "BP_PlayerState_C_2147482203"]
+        ],
+        "savePath": "/home/omegga/server/data/Saved/Builds",
+        "path": "/home/omegga/server"
+    }"#;
+
+    #[test]
+    fn parses_bootstrap() {
+        let bootstrap = serde_json::from_str::<Bootstrap>(BOOTSTRAP).unwrap();
+        assert_eq!(bootstrap.host.unwrap().name, "x");
+        assert_eq!(bootstrap.version, Some(7870));
+        assert_eq!(
+            bootstrap.save_path.as_deref(),
+            Some("/home/omegga/server/data/Saved/Builds")
+        );
+        assert_eq!(bootstrap.path.as_deref(), Some("/home/omegga/server"));
+
+        let names = bootstrap
+            .players
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["x", "y"]);
+        assert_eq!(
+            bootstrap.players[1].controller,
+            "BP_PlayerController_C_2147482210"
+        );
+        assert_eq!(bootstrap.players[1].state, "BP_PlayerState_C_2147482203");
+    }
+
+    #[test]
+    fn parses_players_as_objects_or_arrays() {
+        let players = serde_json::from_str::<Vec<Player>>(
+            r#"[
+                ["n", "i", "c", "s"],
+                { "name": "n", "id": "i", "controller": "c", "state": "s", "host": true }
+            ]"#,
+        )
+        .unwrap();
+        assert_eq!(players[0].id, "i");
+        assert_eq!(players[0].host, None);
+        assert_eq!(players[1].host, Some(true));
+    }
+
+    #[test]
+    fn parses_version() {
+        assert_eq!(VersionInfo::from(Value::from(7870)).changelist, Some(7870));
+        assert_eq!(
+            VersionInfo::from(Value::from("CL7870")).changelist,
+            Some(7870)
+        );
+        assert_eq!(VersionInfo::from(Value::Null).changelist, None);
+    }
+}
@@ -0,0 +1,504 @@
+use std::ops::{Add, AddAssign, Div, Mul, Neg, Sub, SubAssign};
+
+use serde::{Deserialize, Serialize};
+
+use crate::resources::TemplateBounds;
+
+/// The size of one stud on the X and Y axes, in world units.
+pub const STUD: f64 = 10.0;
+
+/// The height of one plate on the Z axis, in world units.
+pub const PLATE: f64 = 4.0;
+
+/// A position or direction in world units.
+///
+/// Serializes as an `[x, y, z]` array, as Omegga sends positions.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(from = "(f64, f64, f64)", into = "(f64, f64, f64)")]
+pub struct Vec3 {
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Vec3 {
+    pub const ZERO: Vec3 = Vec3::new(0.0, 0.0, 0.0);
+
+    /// Create a new vector.
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
+    /// The dot product of two vectors.
+    pub fn dot(self, other: Vec3) -> f64 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    /// The cross product of two vectors.
+    pub fn cross(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// The squared length of the vector.
+    pub fn length_squared(self) -> f64 {
+        self.dot(self)
+    }
+
+    /// The length of the vector.
+    pub fn length(self) -> f64 {
+        self.length_squared().sqrt()
+    }
+
+    /// The squared distance between two points.
+    pub fn distance_squared(self, other: Vec3) -> f64 {
+        (self - other).length_squared()
+    }
+
+    /// The distance between two points.
+    pub fn distance(self, other: Vec3) -> f64 {
+        (self - other).length()
+    }
+
+    /// The vector scaled to a length of 1, or zero if it has no length.
+    pub fn normalize(self) -> Vec3 {
+        let length = self.length();
+        if length == 0.0 {
+            Vec3::ZERO
+        } else {
+            self / length
+        }
+    }
+
+    /// The component-wise minimum of two vectors.
+    pub fn min(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    /// The component-wise maximum of two vectors.
+    pub fn max(self, other: Vec3) -> Vec3 {
+        Vec3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+
+    /// Round each component to the nearest integer, in world units.
+    pub fn round(self) -> IVec3 {
+        IVec3::new(
+            self.x.round() as i32,
+            self.y.round() as i32,
+            self.z.round() as i32,
+        )
+    }
+
+    /// Convert a position in world units to brick grid units (studs on X and Y,
+    /// plates on Z), rounding down.
+    pub fn to_grid(self) -> IVec3 {
+        IVec3::new(
+            (self.x / STUD).floor() as i32,
+            (self.y / STUD).floor() as i32,
+            (self.z / PLATE).floor() as i32,
+        )
+    }
+
+    /// Convert a position in brick grid units (studs on X and Y, plates on Z)
+    /// to world units.
+    pub fn from_grid(grid: IVec3) -> Vec3 {
+        Vec3::new(
+            grid.x as f64 * STUD,
+            grid.y as f64 * STUD,
+            grid.z as f64 * PLATE,
+        )
+    }
+}
+
+impl From<(f64, f64, f64)> for Vec3 {
+    fn from((x, y, z): (f64, f64, f64)) -> Self {
+        Vec3::new(x, y, z)
+    }
+}
+
+impl From<Vec3> for (f64, f64, f64) {
+    fn from(v: Vec3) -> Self {
+        (v.x, v.y, v.z)
+    }
+}
+
+impl From<[f64; 3]> for Vec3 {
+    fn from([x, y, z]: [f64; 3]) -> Self {
+        Vec3::new(x, y, z)
+    }
+}
+
+impl From<IVec3> for Vec3 {
+    fn from(v: IVec3) -> Self {
+        Vec3::new(v.x as f64, v.y as f64, v.z as f64)
+    }
+}
+
+/// An integer position or offset in world units, as used by brick positions
+/// and load offsets.
+///
+/// Serializes as an `[x, y, z]` array.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "(i32, i32, i32)", into = "(i32, i32, i32)")]
+pub struct IVec3 {
+    pub x: i32,
+    pub y: i32,
+    pub z: i32,
+}
+
+impl IVec3 {
+    pub const ZERO: IVec3 = IVec3::new(0, 0, 0);
+
+    /// Create a new vector.
+    pub const fn new(x: i32, y: i32, z: i32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Convert to a floating point vector.
+    pub fn as_vec3(self) -> Vec3 {
+        self.into()
+    }
+
+    /// The component-wise minimum of two vectors.
+    pub fn min(self, other: IVec3) -> IVec3 {
+        IVec3::new(
+            self.x.min(other.x),
+            self.y.min(other.y),
+            self.z.min(other.z),
+        )
+    }
+
+    /// The component-wise maximum of two vectors.
+    pub fn max(self, other: IVec3) -> IVec3 {
+        IVec3::new(
+            self.x.max(other.x),
+            self.y.max(other.y),
+            self.z.max(other.z),
+        )
+    }
+}
+
+impl From<(i32, i32, i32)> for IVec3 {
+    fn from((x, y, z): (i32, i32, i32)) -> Self {
+        IVec3::new(x, y, z)
+    }
+}
+
+impl From<IVec3> for (i32, i32, i32) {
+    fn from(v: IVec3) -> Self {
+        (v.x, v.y, v.z)
+    }
+}
+
+impl From<[i32; 3]> for IVec3 {
+    fn from([x, y, z]: [i32; 3]) -> Self {
+        IVec3::new(x, y, z)
+    }
+}
+
+macro_rules! vec_ops {
+    ($t:ident, $s:ty) => {
+        impl Add for $t {
+            type Output = $t;
+
+            fn add(self, rhs: $t) -> $t {
+                $t::new(self.x + rhs.x, self.y + rhs.y, self.z + rhs.z)
+            }
+        }
+
+        impl Sub for $t {
+            type Output = $t;
+
+            fn sub(self, rhs: $t) -> $t {
+                $t::new(self.x - rhs.x, self.y - rhs.y, self.z - rhs.z)
+            }
+        }
+
+        impl Mul<$s> for $t {
+            type Output = $t;
+
+            fn mul(self, rhs: $s) -> $t {
+                $t::new(self.x * rhs, self.y * rhs, self.z * rhs)
+            }
+        }
+
+        impl Div<$s> for $t {
+            type Output = $t;
+
+            fn div(self, rhs: $s) -> $t {
+                $t::new(self.x / rhs, self.y / rhs, self.z / rhs)
+            }
+        }
+
+        impl Neg for $t {
+            type Output = $t;
+
+            fn neg(self) -> $t {
+                $t::new(-self.x, -self.y, -self.z)
+            }
+        }
+
+        impl AddAssign for $t {
+            fn add_assign(&mut self, rhs: $t) {
+                *self = *self + rhs;
+            }
+        }
+
+        impl SubAssign for $t {
+            fn sub_assign(&mut self, rhs: $t) {
+                *self = *self - rhs;
+            }
+        }
+    };
+}
+
+vec_ops!(Vec3, f64);
+vec_ops!(IVec3, i32);
+
+/// An axis-aligned bounding box, in world units.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Create a bounding box from two opposite corners.
+    pub fn new(a: impl Into<Vec3>, b: impl Into<Vec3>) -> Self {
+        let (a, b) = (a.into(), b.into());
+        Self {
+            min: a.min(b),
+            max: a.max(b),
+        }
+    }
+
+    /// Create a bounding box from its center and half of its size on each axis.
+    pub fn from_center(center: impl Into<Vec3>, extent: impl Into<Vec3>) -> Self {
+        let (center, extent) = (center.into(), extent.into());
+        Self::new(center - extent, center + extent)
+    }
+
+    /// The center of the bounding box.
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) / 2.0
+    }
+
+    /// The size of the bounding box on each axis.
+    pub fn size(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// Whether a point is inside the bounding box, including its edges.
+    pub fn contains(&self, point: impl Into<Vec3>) -> bool {
+        let p = point.into();
+        p.x >= self.min.x
+            && p.x <= self.max.x
+            && p.y >= self.min.y
+            && p.y <= self.max.y
+            && p.z >= self.min.z
+            && p.z <= self.max.z
+    }
+
+    /// Whether another bounding box is entirely inside this one.
+    pub fn contains_aabb(&self, other: &Aabb) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    /// Whether two bounding boxes overlap, including touching edges.
+    pub fn intersects(&self, other: &Aabb) -> bool {
+        self.min.x <= other.max.x
+            && self.max.x >= other.min.x
+            && self.min.y <= other.max.y
+            && self.max.y >= other.min.y
+            && self.min.z <= other.max.z
+            && self.max.z >= other.min.z
+    }
+
+    /// The overlap of two bounding boxes, if they intersect.
+    pub fn intersection(&self, other: &Aabb) -> Option<Aabb> {
+        self.intersects(other).then(|| Aabb {
+            min: self.min.max(other.min),
+            max: self.max.min(other.max),
+        })
+    }
+
+    /// The smallest bounding box containing both bounding boxes.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.min(other.min),
+            max: self.max.max(other.max),
+        }
+    }
+
+    /// Grow the bounding box by an amount on every side.
+    pub fn expand(&self, amount: f64) -> Aabb {
+        let amount = Vec3::new(amount, amount, amount);
+        Aabb {
+            min: self.min - amount,
+            max: self.max + amount,
+        }
+    }
+}
+
+impl From<&TemplateBounds> for Aabb {
+    fn from(bounds: &TemplateBounds) -> Self {
+        Aabb::new(bounds.min_bound, bounds.max_bound)
+    }
+}
+
+impl From<TemplateBounds> for Aabb {
+    fn from(bounds: TemplateBounds) -> Self {
+        Aabb::from(&bounds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn does_vector_math() {
+        let a = Vec3::new(1.0, 2.0, 3.0);
+        let b = Vec3::new(4.0, 5.0, 6.0);
+        assert_eq!(a + b, Vec3::new(5.0, 7.0, 9.0));
+        assert_eq!(b - a, Vec3::new(3.0, 3.0, 3.0));
+        assert_eq!(a * 2.0, Vec3::new(2.0, 4.0, 6.0));
+        assert_eq!(b / 2.0, Vec3::new(2.0, 2.5, 3.0));
+        assert_eq!(-a, Vec3::new(-1.0, -2.0, -3.0));
+        assert_eq!(a.dot(b), 32.0);
+        assert_eq!(
+            Vec3::new(1.0, 0.0, 0.0).cross(Vec3::new(0.0, 1.0, 0.0)),
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(Vec3::new(3.0, 4.0, 0.0).length(), 5.0);
+        assert_eq!(Vec3::ZERO.distance(Vec3::new(0.0, 3.0, 4.0)), 5.0);
+        assert_eq!(
+            Vec3::new(0.0, 0.0, 2.0).normalize(),
+            Vec3::new(0.0, 0.0, 1.0)
+        );
+        assert_eq!(Vec3::ZERO.normalize(), Vec3::ZERO);
+        assert_eq!(a.min(Vec3::new(0.0, 5.0, 3.0)), Vec3::new(0.0, 2.0, 3.0));
+        assert_eq!(a.max(Vec3::new(0.0, 5.0, 3.0)), Vec3::new(1.0, 5.0, 3.0));
+
+        let mut c = a;
+        c += b;
+        c -= a;
+        assert_eq!(c, b);
+    }
+
+    #[test]
+    fn does_integer_vector_math() {
+        let a = IVec3::new(1, -2, 3);
+        let b = IVec3::new(4, 5, -6);
+        assert_eq!(a + b, IVec3::new(5, 3, -3));
+        assert_eq!(a - b, IVec3::new(-3, -7, 9));
+        assert_eq!(a * 3, IVec3::new(3, -6, 9));
+        assert_eq!(b / 2, IVec3::new(2, 2, -3));
+        assert_eq!(-a, IVec3::new(-1, 2, -3));
+        assert_eq!(a.min(b), IVec3::new(1, -2, -6));
+        assert_eq!(a.max(b), IVec3::new(4, 5, 3));
+        assert_eq!(a.as_vec3(), Vec3::new(1.0, -2.0, 3.0));
+    }
+
+    #[test]
+    fn serializes_as_arrays() {
+        let v = Vec3::new(1.5, -2.0, 3.0);
+        assert_eq!(serde_json::to_string(&v).unwrap(), "[1.5,-2.0,3.0]");
+        assert_eq!(serde_json::from_str::<Vec3>("[1.5,-2,3]").unwrap(), v);
+
+        let v = IVec3::new(1, -2, 3);
+        assert_eq!(serde_json::to_string(&v).unwrap(), "[1,-2,3]");
+        assert_eq!(serde_json::from_str::<IVec3>("[1,-2,3]").unwrap(), v);
+    }
+
+    #[test]
+    fn rounds_to_nearest() {
+        assert_eq!(Vec3::new(1.4, 1.5, -1.5).round(), IVec3::new(1, 2, -2));
+    }
+
+    #[test]
+    fn converts_grid_units() {
+        assert_eq!(Vec3::new(0.0, 9.9, 3.9).to_grid(), IVec3::new(0, 0, 0));
+        assert_eq!(Vec3::new(10.0, 25.0, 8.0).to_grid(), IVec3::new(1, 2, 2));
+        // negative positions round down, not towards zero
+        assert_eq!(
+            Vec3::new(-0.1, -10.0, -4.1).to_grid(),
+            IVec3::new(-1, -1, -2)
+        );
+
+        assert_eq!(
+            Vec3::from_grid(IVec3::new(1, -2, 3)),
+            Vec3::new(10.0, -20.0, 12.0)
+        );
+        let grid = IVec3::new(-3, 7, 11);
+        assert_eq!(Vec3::from_grid(grid).to_grid(), grid);
+    }
+
+    #[test]
+    fn normalizes_corners() {
+        let aabb = Aabb::new((10.0, 0.0, 5.0), (0.0, 10.0, -5.0));
+        assert_eq!(aabb.min, Vec3::new(0.0, 0.0, -5.0));
+        assert_eq!(aabb.max, Vec3::new(10.0, 10.0, 5.0));
+        assert_eq!(aabb.center(), Vec3::new(5.0, 5.0, 0.0));
+        assert_eq!(aabb.size(), Vec3::new(10.0, 10.0, 10.0));
+        assert_eq!(Aabb::from_center((5.0, 5.0, 0.0), (5.0, 5.0, 5.0)), aabb);
+    }
+
+    #[test]
+    fn contains_points_on_edges() {
+        let aabb = Aabb::new((0.0, 0.0, 0.0), (10.0, 10.0, 10.0));
+        assert!(aabb.contains((5.0, 5.0, 5.0)));
+        assert!(aabb.contains((0.0, 0.0, 0.0)));
+        assert!(aabb.contains((10.0, 10.0, 10.0)));
+        assert!(aabb.contains((10.0, 0.0, 5.0)));
+        assert!(!aabb.contains((10.1, 5.0, 5.0)));
+        assert!(!aabb.contains((5.0, -0.1, 5.0)));
+        assert!(!aabb.contains((5.0, 5.0, 10.1)));
+
+        assert!(aabb.contains_aabb(&Aabb::new((0.0, 0.0, 0.0), (10.0, 5.0, 5.0))));
+        assert!(!aabb.contains_aabb(&Aabb::new((5.0, 5.0, 5.0), (15.0, 5.0, 5.0))));
+    }
+
+    #[test]
+    fn intersects_and_combines() {
+        let a = Aabb::new((0.0, 0.0, 0.0), (10.0, 10.0, 10.0));
+        let b = Aabb::new((5.0, 5.0, 5.0), (15.0, 15.0, 15.0));
+        let touching = Aabb::new((10.0, 0.0, 0.0), (20.0, 10.0, 10.0));
+        let apart = Aabb::new((11.0, 0.0, 0.0), (20.0, 10.0, 10.0));
+
+        assert!(a.intersects(&b) && b.intersects(&a));
+        assert!(a.intersects(&touching));
+        assert!(!a.intersects(&apart) && !apart.intersects(&a));
+
+        assert_eq!(
+            a.intersection(&b),
+            Some(Aabb::new((5.0, 5.0, 5.0), (10.0, 10.0, 10.0)))
+        );
+        assert_eq!(
+            a.intersection(&touching),
+            Some(Aabb::new((10.0, 0.0, 0.0), (10.0, 10.0, 10.0)))
+        );
+        assert_eq!(a.intersection(&apart), None);
+
+        assert_eq!(
+            a.union(&apart),
+            Aabb::new((0.0, 0.0, 0.0), (20.0, 10.0, 10.0))
+        );
+        assert_eq!(a.union(&b).center(), Vec3::new(7.5, 7.5, 7.5));
+        assert_eq!(
+            a.expand(1.0),
+            Aabb::new((-1.0, -1.0, -1.0), (11.0, 11.0, 11.0))
+        );
+    }
+}
@@ -3,36 +3,74 @@ use std::{
     pin::Pin,
     sync::{
         atomic::{AtomicI32, Ordering},
-        Arc,
+        Arc, RwLock,
     },
     task::{Context, Poll},
 };
+#[cfg(feature = "tokio")]
+use std::{io::Write, time::Duration};
 
 #[cfg(feature = "brs")]
 use brickadia::save;
 
+#[cfg(feature = "tokio")]
+use console::{Capture, CaptureTimeout};
+use console::{ConsoleCommand, InvalidArgument, LineCapture};
 use dashmap::{mapref::entry::Entry, DashMap};
-use events::{BrickInteraction, Event};
-use resources::{GhostBrick, Player, PlayerPaint, Plugin, TemplateBounds};
+use events::{AutorestartNotice, Bootstrap, BrickInteraction, Event, VersionInfo};
+use geometry::{IVec3, Vec3};
+use resources::{
+    BanList, Environment, GhostBrick, Host, Minigame, Permissions, Player, PlayerPaint, Plugin,
+    RoleSetup, ServerInfo, TemplateBounds,
+};
+#[cfg(feature = "tokio")]
+use scheduler::Scheduler;
 use serde_json::{json, Value};
+use session::{Direction, Session};
+#[cfg(feature = "tokio")]
+use shutdown::ShutdownHooks;
 use thiserror::Error;
+use tokio::sync::{mpsc::UnboundedReceiver, oneshot};
+#[cfg(feature = "tokio")]
 use tokio::{
     io::{stdin, AsyncBufReadExt, BufReader},
-    sync::{
-        mpsc::{self, UnboundedReceiver},
-        oneshot,
-    },
+    runtime::Handle,
+    sync::mpsc,
+    task::JoinHandle,
+    time::{self, Instant},
 };
 
 use crate::resources::PlayerPosition;
 
+#[cfg(feature = "tokio")]
+pub mod blocking;
+pub mod chat;
+#[cfg(feature = "tokio")]
+pub mod commands;
+pub mod console;
 pub mod events;
+pub mod geometry;
+#[cfg(feature = "log")]
+pub mod logging;
+pub mod orientation;
+pub mod panics;
+#[cfg(feature = "tokio")]
+pub mod queue;
 pub mod resources;
 pub mod rpc;
+#[cfg(feature = "tokio")]
+pub mod scheduler;
+pub mod session;
+#[cfg(feature = "tokio")]
+pub mod shutdown;
+#[cfg(feature = "tokio")]
+pub mod tracker;
+#[cfg(feature = "tokio")]
+pub mod zones;
 
 pub type EventReceiver = UnboundedReceiver<Event>;
 
-/// A future that waits for the server to respond, returning a [`Response`](crate::Response).
+/// A future that waits for the server to respond, returning a [`Response`](rpc::Response).
 /// This will await indefinitely, so use with Tokio's `select!` macro to impose a timeout.
 pub struct ResponseAwaiter(oneshot::Receiver<rpc::Response>);
 
@@ -66,9 +104,33 @@ pub enum ResponseError {
     Recv(#[from] oneshot::error::RecvError),
 }
 
+/// Decode the parameters of a message, returning `None` if they are missing or malformed.
+fn decode_params<T: serde::de::DeserializeOwned>(params: Option<Value>) -> Option<T> {
+    serde_json::from_value(params?).ok()
+}
+
+/// Get the first parameter of a notification, which is usually sent as a single-item array.
+fn first_param(params: Option<Value>) -> Value {
+    match params {
+        Some(Value::Array(params)) => params.into_iter().next().unwrap_or(Value::Null),
+        Some(params) => params,
+        None => Value::Null,
+    }
+}
+
+#[derive(Clone)]
 pub struct Omegga {
     pub awaiter_txs: Arc<DashMap<rpc::RequestId, oneshot::Sender<rpc::Response>>>,
     request_id: Arc<AtomicI32>,
+    #[cfg(feature = "tokio")]
+    runtime: Option<Handle>,
+    #[cfg(feature = "tokio")]
+    scheduler: Scheduler,
+    #[cfg(feature = "tokio")]
+    shutdown_hooks: Arc<ShutdownHooks>,
+    line_capture: Arc<LineCapture>,
+    server_info: Arc<RwLock<ServerInfo>>,
+    session: Arc<Session>,
 }
 
 impl Omegga {
@@ -77,246 +139,432 @@ impl Omegga {
         Self {
             awaiter_txs: Arc::new(DashMap::new()),
             request_id: Arc::new(AtomicI32::new(-1)),
+            #[cfg(feature = "tokio")]
+            runtime: Handle::try_current().ok(),
+            #[cfg(feature = "tokio")]
+            scheduler: Scheduler::new(),
+            #[cfg(feature = "tokio")]
+            shutdown_hooks: Arc::new(ShutdownHooks::default()),
+            line_capture: Arc::new(LineCapture::default()),
+            server_info: Arc::new(RwLock::new(ServerInfo::default())),
+            session: Arc::new(Session::default()),
         }
     }
 
-    /// Spawn the listener.
+    /// Spawn a background task on the runtime this was created in, or the current runtime
+    /// if it was created outside of one.
+    #[cfg(feature = "tokio")]
+    pub(crate) fn spawn_task<F>(&self, future: F) -> JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        match &self.runtime {
+            Some(runtime) => runtime.spawn(future),
+            None => tokio::spawn(future),
+        }
+    }
+
+    /// Get the scheduler for delayed, repeating and cron jobs. Its jobs are cancelled
+    /// when the plugin is told to stop.
+    #[cfg(feature = "tokio")]
+    pub fn scheduler(&self) -> &Scheduler {
+        &self.scheduler
+    }
+
+    /// Get what's known about the server, like its host and current map.
+    ///
+    /// This is filled in from events as they are received by
+    /// [`handle_message`](Omegga::handle_message).
+    pub fn server_info(&self) -> ServerInfo {
+        self.server_info.read().unwrap().clone()
+    }
+
+    /// Register an async hook to run when the plugin shuts down through
+    /// [`shutdown`](Omegga::shutdown).
+    #[cfg(feature = "tokio")]
+    pub fn on_shutdown<F, Fut>(&self, hook: F)
+    where
+        F: FnOnce(Omegga) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.shutdown_hooks.add(hook);
+    }
+
+    /// Shut down the plugin gracefully in response to [`Event::Stop`].
+    ///
+    /// This cancels scheduled jobs, including position trackers, runs every shutdown hook
+    /// concurrently, waits for pending [`request`](Omegga::request)s that are still being
+    /// awaited to be answered, flushes output, responds to the stop request, then exits the
+    /// process. Hooks and pending requests that haven't finished by `deadline` are abandoned.
+    #[cfg(feature = "tokio")]
+    pub async fn shutdown(&self, id: rpc::RequestId, deadline: Duration) -> ! {
+        let deadline = Instant::now() + deadline;
+        self.scheduler().cancel_all();
+
+        let hooks = self
+            .shutdown_hooks
+            .take()
+            .into_iter()
+            .map(|hook| self.spawn_task(hook(self.clone())))
+            .collect::<Vec<_>>();
+        for hook in hooks {
+            let _ = time::timeout_at(deadline, hook).await;
+        }
+
+        loop {
+            // requests whose awaiter was dropped, like after a timeout, will never be awaited
+            self.awaiter_txs.retain(|_, tx| !tx.is_closed());
+            if self.awaiter_txs.is_empty() || Instant::now() >= deadline {
+                break;
+            }
+            time::sleep(Duration::from_millis(10)).await;
+        }
+
+        let _ = std::io::stdout().flush();
+        self.write_response(id, None, None);
+        let _ = std::io::stdout().flush();
+        std::process::exit(0);
+    }
+
+    /// Record every message received and sent to a JSONL file, replacing any recording in
+    /// progress. Load the file with [`Recording::load`](session::Recording::load).
+    #[cfg_attr(feature = "tokio", doc = "Replay it with [`replay`](Omegga::replay).")]
+    pub fn record(&self, path: impl AsRef<std::path::Path>) -> std::io::Result<()> {
+        self.session.start_recording(path)
+    }
+
+    /// Stop recording, if recording.
+    pub fn stop_recording(&self) {
+        self.session.stop_recording();
+    }
+
+    /// Replay a recording instead of reading from stdin, returning a receiver for the
+    /// decoded events, like [`spawn`](Omegga::spawn). The receiver closes once every
+    /// message has been replayed.
+    ///
+    /// Messages the plugin writes are kept instead of being printed; get them with
+    /// [`replay_output`](Omegga::replay_output) and compare them to the recording with
+    /// [`Recording::diff_output`](session::Recording::diff_output) or
+    /// [`Recording::assert_output`](session::Recording::assert_output).
+    ///
+    /// If `realtime` is set, messages are replayed with their recorded timing. Recorded
+    /// responses wait for the plugin to send the matching request, so requests must be
+    /// made in the same order as when recording.
+    #[cfg(feature = "tokio")]
+    pub fn replay(&self, recording: session::Recording, realtime: bool) -> EventReceiver {
+        const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+
+        self.session.start_replay();
+        let (tx, rx) = mpsc::unbounded_channel::<Event>();
+        let omegga = self.clone();
+        self.spawn_task(async move {
+            let start = Instant::now();
+            for entry in recording.incoming() {
+                if realtime {
+                    time::sleep_until(start + Duration::from_millis(entry.time)).await;
+                }
+
+                if let rpc::Message::Response { id, .. } = &entry.message {
+                    let deadline = Instant::now() + RESPONSE_TIMEOUT;
+                    while !omegga.awaiter_txs.contains_key(id) && Instant::now() < deadline {
+                        time::sleep(Duration::from_millis(1)).await;
+                    }
+                }
+
+                if let Some(event) = omegga.handle_message(entry.message.clone()) {
+                    let _ = tx.send(event);
+                }
+            }
+        });
+        rx
+    }
+
+    /// Take the messages the plugin has written since a replay started, or since this was
+    /// last called.
+    #[cfg_attr(feature = "tokio", doc = "See [`replay`](Omegga::replay).")]
+    pub fn replay_output(&self) -> Vec<rpc::Message> {
+        self.session.take_replay_output()
+    }
+
+    /// Spawn the listener, which reads messages from stdin and handles them with
+    /// [`handle_line`](Omegga::handle_line).
+    #[cfg(feature = "tokio")]
     pub fn spawn(&self) -> EventReceiver {
         let (tx, rx) = mpsc::unbounded_channel::<Event>();
-        let awaiter_txs = Arc::clone(&self.awaiter_txs);
-        tokio::spawn(async move {
+        let omegga = self.clone();
+        self.spawn_task(async move {
             let reader = BufReader::new(stdin());
             let mut lines = reader.lines();
             while let Some(line) = lines.next_line().await.unwrap() {
-                let message: rpc::Message = match serde_json::from_str(&line) {
-                    Ok(v) => v,
-                    Err(_) => continue,
-                };
-
-                match message {
-                    // Handle responses
-                    rpc::Message::Response {
-                        id, result, error, ..
-                    } => {
-                        if let Entry::Occupied(entry) = awaiter_txs.entry(id) {
-                            let (id, sender) = entry.remove_entry();
-                            let _ = sender.send(rpc::Response { id, result, error });
-                        }
-                    }
-                    // Handle requests
-                    rpc::Message::Request {
-                        id, method, params, ..
-                    } => match method.as_str() {
-                        "init" => {
-                            let _ = tx.send(Event::Init {
-                                id,
-                                config: params.unwrap_or(Value::Null),
-                            });
-                        }
-                        "stop" => {
-                            let _ = tx.send(Event::Stop { id });
-                        }
-                        "plugin:emit" => match params {
-                            Some(Value::Array(v)) => {
-                                let mut params = v.into_iter();
-                                let event = match params.next().unwrap() {
-                                    Value::String(s) => s,
-                                    _ => continue,
-                                };
-                                let from = match params.next().unwrap() {
-                                    Value::String(s) => s,
-                                    _ => continue,
-                                };
-
-                                let _ = tx.send(Event::PluginEmit {
-                                    id,
-                                    event,
-                                    from,
-                                    args: params.collect(),
-                                });
-                            }
-                            _ => (),
-                        },
-                        _ => (),
-                    },
-                    // Handle notifications
-                    rpc::Message::Notification { method, params, .. } => match method.as_str() {
-                        "bootstrap" => {
-                            let _ = tx.send(Event::Bootstrap {
-                                omegga: params.unwrap_or(Value::Null),
-                            });
-                        }
-                        "plugin:players:raw" => {
-                            let _ = tx.send(Event::PluginPlayersRaw {
-                                players: serde_json::from_value(params.unwrap())
-                                    .unwrap_or_default(),
-                            });
-                        }
-                        "line" => {
-                            let _ = tx.send(Event::Line(
-                                serde_json::from_value::<Vec<String>>(params.unwrap())
-                                    .unwrap()
-                                    .into_iter()
-                                    .next()
-                                    .unwrap(),
-                            ));
-                        }
-                        "start" => {
-                            #[derive(serde::Deserialize)]
-                            struct MapParams {
-                                map: String,
-                            }
-
-                            let _ = tx.send(Event::Start {
-                                map: serde_json::from_value::<Vec<MapParams>>(params.unwrap())
-                                    .unwrap()
-                                    .into_iter()
-                                    .next()
-                                    .unwrap()
-                                    .map,
-                            });
-                        }
-                        "host" => {
-                            #[derive(serde::Deserialize)]
-                            struct HostParams {
-                                name: String,
-                                id: String,
-                            }
-
-                            let host = serde_json::from_value::<Vec<HostParams>>(params.unwrap())
-                                .unwrap()
-                                .into_iter()
-                                .next()
-                                .unwrap();
-
-                            let _ = tx.send(Event::Host {
-                                name: host.name,
-                                id: host.id,
-                            });
-                        }
-                        "version" => {
-                            let _ = tx.send(Event::Version(params.unwrap()));
-                        }
-                        "unauthorized" => {
-                            let _ = tx.send(Event::Unauthorized);
-                        }
-                        "join" => {
-                            let _ = tx.send(Event::Join(
-                                serde_json::from_value::<Vec<_>>(params.unwrap())
-                                    .unwrap()
-                                    .into_iter()
-                                    .next()
-                                    .unwrap(),
-                            ));
-                        }
-                        "leave" => {
-                            let _ = tx.send(Event::Leave(
-                                serde_json::from_value::<Vec<_>>(params.unwrap())
-                                    .unwrap()
-                                    .into_iter()
-                                    .next()
-                                    .unwrap(),
-                            ));
-                        }
-                        e if e.starts_with("cmd:") => {
-                            let c = &e[4..];
-                            let mut params = serde_json::from_value::<Vec<String>>(params.unwrap())
-                                .unwrap()
-                                .into_iter();
-
-                            let _ = tx.send(Event::Command {
-                                player: params.next().unwrap(),
-                                command: c.to_string(),
-                                args: params.collect(),
-                            });
-                        }
-                        e if e.starts_with("chatcmd:") => {
-                            let c = &e[8..];
-                            let mut params = serde_json::from_value::<Vec<String>>(params.unwrap())
-                                .unwrap()
-                                .into_iter();
-
-                            let _ = tx.send(Event::ChatCommand {
-                                player: params.next().unwrap(),
-                                command: c.to_string(),
-                                args: params.collect(),
-                            });
-                        }
-                        "chat" => {
-                            let mut params = serde_json::from_value::<Vec<String>>(params.unwrap())
-                                .unwrap()
-                                .into_iter();
-
-                            let _ = tx.send(Event::Chat {
-                                player: params.next().unwrap(),
-                                message: params.next().unwrap(),
-                            });
-                        }
-                        "mapchange" => {
-                            #[derive(serde::Deserialize)]
-                            struct MapParams {
-                                map: String,
-                            }
-
-                            let _ = tx.send(Event::MapChange(
-                                serde_json::from_value::<Vec<MapParams>>(params.unwrap())
-                                    .unwrap()
-                                    .into_iter()
-                                    .next()
-                                    .unwrap()
-                                    .map,
-                            ));
-                        }
-                        "interact" => {
-                            match params {
-                              Some(Value::Array(params)) => {
-                                  let mut params = params.into_iter();
-                                  let interact =
-                                      serde_json::from_value::<BrickInteraction>(params.next().unwrap())
-                                          .unwrap();
-
-                                  let _ = tx.send(Event::Interact(interact));
-                              }
-                              _ => continue,
-                            }
-                        }
-                        e if e.starts_with("event:") => {
-                            let e = &e[6..];
-                            match params {
-                                Some(Value::Array(params)) => {
-                                    let mut params = params.into_iter();
-                                    let player =
-                                        serde_json::from_value::<Player>(params.next().unwrap())
-                                            .unwrap();
-                                    let args = params
-                                        .map(|a| String::from(a.as_str().unwrap()))
-                                        .collect::<Vec<_>>();
-
-                                    let _ = tx.send(Event::Event {
-                                        name: String::from(e),
-                                        player,
-                                        args,
-                                    });
-                                }
-                                _ => continue,
-                            }
-                        }
-                        "autorestart" => {
-                            let _ = tx.send(Event::Autorestart(params.unwrap_or_default()));
-                        }
-                        _ => (),
-                    },
-                };
+                if let Some(event) = omegga.handle_line(&line) {
+                    let _ = tx.send(event);
+                }
             }
         });
         rx
     }
 
+    /// Handle a line of input from Omegga. See [`handle_message`](Omegga::handle_message).
+    ///
+    /// Lines that aren't valid RPC messages, or messages with missing or malformed
+    /// parameters, are ignored.
+    pub fn handle_line(&self, line: &str) -> Option<Event> {
+        serde_json::from_str::<rpc::Message>(line)
+            .ok()
+            .and_then(|message| self.handle_message(message))
+    }
+
+    /// Handle a message from Omegga, without needing a runtime.
+    ///
+    /// Responses are passed to the [`request`](Omegga::request) waiting for them, and
+    /// requests and notifications are decoded into an [`Event`]. Call it directly to drive
+    /// the plugin from another executor or a manual loop.
+    #[cfg_attr(
+        feature = "tokio",
+        doc = "This is what [`spawn`](Omegga::spawn) does for each line it reads."
+    )]
+    ///
+    /// Messages with missing or malformed parameters produce no event.
+    pub fn handle_message(&self, message: rpc::Message) -> Option<Event> {
+        self.session.record(Direction::In, &message);
+        let mut event = None;
+        match message {
+            // Handle responses
+            rpc::Message::Response {
+                id, result, error, ..
+            } => {
+                if let Entry::Occupied(entry) = self.awaiter_txs.entry(id) {
+                    let (id, sender) = entry.remove_entry();
+                    let _ = sender.send(rpc::Response { id, result, error });
+                }
+            }
+            // Handle requests
+            rpc::Message::Request {
+                id, method, params, ..
+            } => match method.as_str() {
+                "init" => {
+                    event = Some(Event::Init {
+                        id,
+                        config: params.unwrap_or(Value::Null),
+                    });
+                }
+                "stop" => {
+                    #[cfg(feature = "tokio")]
+                    self.scheduler.cancel_all();
+                    event = Some(Event::Stop { id });
+                }
+                "plugin:emit" => {
+                    if let Some(Value::Array(v)) = params {
+                        let mut params = v.into_iter();
+                        let name = match params.next()? {
+                            Value::String(s) => s,
+                            _ => return None,
+                        };
+                        let from = match params.next()? {
+                            Value::String(s) => s,
+                            _ => return None,
+                        };
+
+                        event = Some(Event::PluginEmit {
+                            id,
+                            event: name,
+                            from,
+                            args: params.collect(),
+                        });
+                    }
+                }
+                _ => (),
+            },
+            // Handle notifications
+            rpc::Message::Notification { method, params, .. } => match method.as_str() {
+                "bootstrap" => {
+                    let bootstrap =
+                        serde_json::from_value::<Bootstrap>(first_param(params)).ok()?;
+
+                    let mut info = self.server_info.write().unwrap();
+                    info.host = bootstrap.host.clone().or(info.host.take());
+                    info.map = bootstrap.map.clone().or(info.map.take());
+                    info.version = bootstrap.version.or(info.version);
+                    info.path = bootstrap.path.clone().or(info.path.take());
+                    info.save_path = bootstrap.save_path.clone().or(info.save_path.take());
+                    drop(info);
+
+                    event = Some(Event::Bootstrap(bootstrap));
+                }
+                "plugin:players:raw" => {
+                    event = Some(Event::PluginPlayersRaw {
+                        players: decode_params(params).unwrap_or_default(),
+                    });
+                }
+                "line" => {
+                    let line = decode_params::<Vec<String>>(params)?.into_iter().next()?;
+                    self.line_capture.forward(&line);
+                    event = Some(Event::Line(line));
+                }
+                "start" => {
+                    #[derive(serde::Deserialize)]
+                    struct MapParams {
+                        map: String,
+                    }
+
+                    let map = decode_params::<Vec<MapParams>>(params)?
+                        .into_iter()
+                        .next()?
+                        .map;
+                    self.server_info.write().unwrap().map = Some(map.clone());
+                    event = Some(Event::Start { map });
+                }
+                "host" => {
+                    #[derive(serde::Deserialize)]
+                    struct HostParams {
+                        name: String,
+                        id: String,
+                    }
+
+                    let host = decode_params::<Vec<HostParams>>(params)?
+                        .into_iter()
+                        .next()?;
+
+                    self.server_info.write().unwrap().host = Some(Host {
+                        name: host.name.clone(),
+                        id: host.id.clone(),
+                    });
+                    event = Some(Event::Host {
+                        name: host.name,
+                        id: host.id,
+                    });
+                }
+                "version" => {
+                    let version = VersionInfo::from(first_param(params));
+                    // keep the known version if this one can't be parsed
+                    if let Some(changelist) = version.changelist {
+                        self.server_info.write().unwrap().version = Some(changelist);
+                    }
+                    event = Some(Event::Version(version));
+                }
+                "unauthorized" => {
+                    event = Some(Event::Unauthorized);
+                }
+                "join" => {
+                    event = Some(Event::Join(
+                        decode_params::<Vec<_>>(params)?.into_iter().next()?,
+                    ));
+                }
+                "leave" => {
+                    let player: Player = decode_params::<Vec<_>>(params)?.into_iter().next()?;
+
+                    #[cfg(feature = "tokio")]
+                    {
+                        self.scheduler.cancel_player(&player.name);
+                        self.scheduler.cancel_player(&player.id);
+                    }
+                    event = Some(Event::Leave(player));
+                }
+                e if e.starts_with("cmd:") => {
+                    let c = &e[4..];
+                    let mut params = decode_params::<Vec<String>>(params)?.into_iter();
+
+                    event = Some(Event::Command {
+                        player: params.next()?,
+                        command: c.to_string(),
+                        args: params.collect(),
+                    });
+                }
+                e if e.starts_with("chatcmd:") => {
+                    let c = &e[8..];
+                    let mut params = decode_params::<Vec<String>>(params)?.into_iter();
+
+                    event = Some(Event::ChatCommand {
+                        player: params.next()?,
+                        command: c.to_string(),
+                        args: params.collect(),
+                    });
+                }
+                "chat" => {
+                    let mut params = decode_params::<Vec<String>>(params)?.into_iter();
+
+                    event = Some(Event::Chat {
+                        player: params.next()?,
+                        message: params.next()?,
+                    });
+                }
+                "mapchange" => {
+                    #[derive(serde::Deserialize)]
+                    struct MapParams {
+                        map: String,
+                    }
+
+                    let map = decode_params::<Vec<MapParams>>(params)?
+                        .into_iter()
+                        .next()?
+                        .map;
+                    self.server_info.write().unwrap().map = Some(map.clone());
+                    event = Some(Event::MapChange(map));
+                }
+                "interact" => match params {
+                    Some(Value::Array(params)) => {
+                        let mut params = params.into_iter();
+                        let interact =
+                            serde_json::from_value::<BrickInteraction>(params.next()?).ok()?;
+
+                        event = Some(Event::Interact(interact));
+                    }
+                    _ => return None,
+                },
+                e if e.starts_with("event:") => {
+                    let e = &e[6..];
+                    match params {
+                        Some(Value::Array(params)) => {
+                            let mut params = params.into_iter();
+                            let player = serde_json::from_value::<Player>(params.next()?).ok()?;
+                            let args = params
+                                .map(|a| a.as_str().map(String::from))
+                                .collect::<Option<Vec<_>>>()?;
+
+                            event = Some(Event::Event {
+                                name: String::from(e),
+                                player,
+                                args,
+                            });
+                        }
+                        _ => return None,
+                    }
+                }
+                "autorestart" => {
+                    event = Some(Event::Autorestart(
+                        serde_json::from_value::<AutorestartNotice>(first_param(params))
+                            .unwrap_or_default(),
+                    ));
+                }
+                _ => (),
+            },
+        };
+        event
+    }
+
     /// Write out an RPC message.
     pub fn write(&self, message: rpc::Message) {
+        self.session.record(Direction::Out, &message);
+        if self.session.capture(&message) {
+            return;
+        }
         println!("{}", serde_json::to_string(&message).unwrap());
     }
 
+    /// Write out an RPC message from the panic hook, which may run while the panicking
+    /// thread holds the session, so it's skipped rather than waited for.
+    pub(crate) fn write_from_panic_hook(&self, message: rpc::Message) {
+        self.session.try_record(Direction::Out, &message);
+        if self.session.try_capture(&message) {
+            return;
+        }
+        if let Ok(line) = serde_json::to_string(&message) {
+            use std::io::Write as _;
+            let _ = writeln!(std::io::stdout().lock(), "{}", line);
+        }
+    }
+
     /// Write out an RPC notification.
     pub fn write_notification(&self, method: impl Into<String>, params: Option<Value>) {
         self.write(rpc::Message::notification(method.into(), params));
@@ -429,6 +677,64 @@ impl Omegga {
         self.write_notification("exec", Some(Value::String(line.into())));
     }
 
+    /// Runs a console command on the Brickadia server.
+    ///
+    /// Nothing is sent if one of the command's arguments can't be quoted safely.
+    pub fn exec_command(&self, command: &ConsoleCommand) -> Result<(), InvalidArgument> {
+        self.writeln(command.line()?);
+        Ok(())
+    }
+
+    /// Writes a line out to the Brickadia server, then collects the console output it
+    /// prints, as chosen by `capture`, for up to `timeout`.
+    ///
+    /// Only one capture runs at a time; others wait for it to finish, so they don't
+    /// collect each other's output. Lines are still sent as [`Event::Line`]s.
+    #[cfg(feature = "tokio")]
+    pub async fn exec_and_capture(
+        &self,
+        line: impl Into<String>,
+        capture: Capture,
+        timeout: Duration,
+    ) -> Result<Vec<String>, CaptureTimeout> {
+        let _guard = self.line_capture.lock.lock().await;
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        *self.line_capture.sink.lock().unwrap() = Some(tx);
+        self.writeln(line);
+
+        let deadline = Instant::now() + timeout;
+        let mut lines = vec![];
+        let result = loop {
+            let line = match time::timeout_at(deadline, rx.recv()).await {
+                Ok(Some(line)) => line,
+                _ => {
+                    break match capture {
+                        Capture::Matching(_) => Ok(lines),
+                        Capture::Until(_) => Err(CaptureTimeout { lines }),
+                    }
+                }
+            };
+
+            match &capture {
+                Capture::Matching(regex) => {
+                    if regex.is_match(console::strip_prefix(&line)) {
+                        lines.push(line);
+                    }
+                }
+                Capture::Until(regex) => {
+                    let done = regex.is_match(console::strip_prefix(&line));
+                    lines.push(line);
+                    if done {
+                        break Ok(lines);
+                    }
+                }
+            }
+        };
+
+        *self.line_capture.sink.lock().unwrap() = None;
+        result
+    }
+
     /// Broadcasts a line.
     pub fn broadcast(&self, line: impl Into<String>) {
         self.write_notification("broadcast", Some(Value::String(line.into())));
@@ -442,6 +748,23 @@ impl Omegga {
         );
     }
 
+    /// Broadcasts text, split across as many lines as needed. See [`chat::split`].
+    pub fn broadcast_long(&self, text: impl AsRef<str>) {
+        for line in chat::split(text.as_ref(), chat::MAX_LINE_LENGTH) {
+            self.broadcast(line);
+        }
+    }
+
+    /// Whispers text to a user by their name, split across as many lines as needed.
+    /// See [`chat::split`].
+    pub fn whisper_long(&self, username: impl Into<String>, text: impl AsRef<str>) {
+        let username = username.into();
+        for line in chat::split(text.as_ref(), chat::MAX_LINE_LENGTH) {
+            self.whisper(username.as_str(), line);
+        }
+    }
+
+    /// Prints a line in the middle of a user's screen by their name.
     pub fn middle_print(&self, username: impl Into<String>, line: impl Into<String>) {
         self.write_notification(
             "middlePrint",
@@ -449,6 +772,29 @@ impl Omegga {
         );
     }
 
+    /// Prints text in the middle of a user's screen by their name, split across as many
+    /// lines as needed. See [`chat::split`].
+    ///
+    /// A middle print replaces the last one, so the lines are joined with `<br>` and sent
+    /// as a single print. Text longer than [`chat::MAX_MIDDLE_PRINT_LENGTH`] is cut off
+    /// after the last line that fits, ending with `...`.
+    pub fn middle_print_long(&self, username: impl Into<String>, text: impl AsRef<str>) {
+        const ELLIPSIS: &str = "...";
+        let limit = chat::MAX_MIDDLE_PRINT_LENGTH - ELLIPSIS.len();
+
+        let mut lines = chat::split(text.as_ref(), chat::MAX_LINE_LENGTH).into_iter();
+        let mut print = lines.next().unwrap_or_default();
+        for line in lines {
+            if print.len() + "<br>".len() + line.len() > limit {
+                print.push_str(ELLIPSIS);
+                break;
+            }
+            print.push_str("<br>");
+            print.push_str(&line);
+        }
+        self.middle_print(username, print);
+    }
+
     /// Gets a list of all players.
     pub async fn get_players(&self) -> Result<Vec<Player>, ResponseError> {
         self.request("getPlayers", None).await.map(|r| match r {
@@ -470,15 +816,55 @@ impl Omegga {
     }
 
     /// Get the role setup.
-    pub async fn get_role_setup(&self) -> Result<Value, ResponseError> {
-        // TODO: write a type for this instead of using a serde_json::Value
-        self.request("getRoleSetup", None).await.map(Option::unwrap)
+    pub async fn get_role_setup(&self) -> Result<Option<RoleSetup>, ResponseError> {
+        self.request("getRoleSetup", None)
+            .await
+            .map(|r| r.and_then(|r| serde_json::from_value::<_>(r).ok()))
     }
 
     /// Get the ban list.
-    pub async fn get_ban_list(&self) -> Result<Value, ResponseError> {
-        // TODO: write a type for this instead of using a serde_json::Value
-        self.request("getBanList", None).await.map(Option::unwrap)
+    pub async fn get_ban_list(&self) -> Result<Option<BanList>, ResponseError> {
+        self.request("getBanList", None)
+            .await
+            .map(|r| r.and_then(|r| serde_json::from_value::<_>(r).ok()))
+    }
+
+    /// Get the current environment.
+    pub async fn get_environment_data(&self) -> Result<Option<Environment>, ResponseError> {
+        self.request("getEnvironmentData", None)
+            .await
+            .map(|r| r.and_then(|r| serde_json::from_value::<_>(r).ok()))
+    }
+
+    /// Get the names of the server's environment presets.
+    pub async fn get_environment_presets(&self) -> Result<Vec<String>, ResponseError> {
+        self.request("getEnvironmentPresets", None)
+            .await
+            .map(|r| match r {
+                Some(r) => serde_json::from_value::<Vec<String>>(r).unwrap_or_else(|_| vec![]),
+                None => vec![],
+            })
+    }
+
+    /// Load a named environment preset.
+    pub async fn load_environment(&self, preset: impl Into<String>) -> Result<(), ResponseError> {
+        self.request("loadEnvironment", Some(Value::String(preset.into())))
+            .await
+            .map(|_| ())
+    }
+
+    /// Load an environment, like one from [`get_environment_data`](Omegga::get_environment_data)
+    /// with some settings changed.
+    pub async fn load_environment_data(
+        &self,
+        environment: &Environment,
+    ) -> Result<(), ResponseError> {
+        self.request(
+            "loadEnvironmentData",
+            Some(serde_json::to_value(environment).unwrap_or(Value::Null)),
+        )
+        .await
+        .map(|_| ())
     }
 
     /// Get a list of the server's saves.
@@ -541,9 +927,10 @@ impl Omegga {
         &self,
         name: impl Into<String>,
         quiet: bool,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
     ) -> Result<(), ResponseError> {
-        self.request("loadBricks", Some(json!({"name": name.into(), "quiet": quiet, "offX": offset.0, "offY": offset.1, "offZ": offset.2}))).await.map(|_| ())
+        let offset = offset.into();
+        self.request("loadBricks", Some(json!({"name": name.into(), "quiet": quiet, "offX": offset.x, "offY": offset.y, "offZ": offset.z}))).await.map(|_| ())
     }
 
     /// Load a save onto a player's clipboard.
@@ -551,9 +938,10 @@ impl Omegga {
         &self,
         name: impl Into<String>,
         player: impl Into<String>,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
     ) -> Result<(), ResponseError> {
-        self.request("loadBricksOnPlayer", Some(json!({"name": name.into(), "player": player.into(), "offX": offset.0, "offY": offset.1, "offZ": offset.2}))).await.map(|_| ())
+        let offset = offset.into();
+        self.request("loadBricksOnPlayer", Some(json!({"name": name.into(), "player": player.into(), "offX": offset.x, "offY": offset.y, "offZ": offset.z}))).await.map(|_| ())
     }
 
     /// Reads a save (from a save file), and returns its data.
@@ -586,9 +974,10 @@ impl Omegga {
         &self,
         data: Value,
         quiet: bool,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
     ) -> Result<(), ResponseError> {
-        self.request("loadSaveData", Some(json!({"data": data, "quiet": quiet, "offX": offset.0, "offY": offset.1, "offZ": offset.2}))).await.map(|_| ())
+        let offset = offset.into();
+        self.request("loadSaveData", Some(json!({"data": data, "quiet": quiet, "offX": offset.x, "offY": offset.y, "offZ": offset.z}))).await.map(|_| ())
     }
 
     /// Loads a save (from brickadia-rs save data) into the world, provided an offset.
@@ -597,9 +986,10 @@ impl Omegga {
         &self,
         data: save::SaveData,
         quiet: bool,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
     ) -> Result<(), ResponseError> {
-        self.request("loadSaveData", Some(json!({"data": data, "quiet": quiet, "offX": offset.0, "offY": offset.1, "offZ": offset.2}))).await.map(|_| ())
+        let offset = offset.into();
+        self.request("loadSaveData", Some(json!({"data": data, "quiet": quiet, "offX": offset.x, "offY": offset.y, "offZ": offset.z}))).await.map(|_| ())
     }
 
     /// Loads a save (from a JSON value) onto a player's clipboard.
@@ -608,9 +998,10 @@ impl Omegga {
         &self,
         data: Value,
         player: impl Into<String>,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
     ) -> Result<(), ResponseError> {
-        self.request("loadSaveDataOnPlayer", Some(json!({"data": data, "player": player.into(), "offX": offset.0, "offY": offset.1, "offZ": offset.2}))).await.map(|_| ())
+        let offset = offset.into();
+        self.request("loadSaveDataOnPlayer", Some(json!({"data": data, "player": player.into(), "offX": offset.x, "offY": offset.y, "offZ": offset.z}))).await.map(|_| ())
     }
 
     /// Loads a save (from brickadia-rs save data) onto a player's clipboard.
@@ -619,9 +1010,10 @@ impl Omegga {
         &self,
         data: save::SaveData,
         player: impl Into<String>,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
     ) -> Result<(), ResponseError> {
-        self.request("loadSaveDataOnPlayer", Some(json!({"data": data, "player": player.into(), "offX": offset.0, "offY": offset.1, "offZ": offset.2}))).await.map(|_| ())
+        let offset = offset.into();
+        self.request("loadSaveDataOnPlayer", Some(json!({"data": data, "player": player.into(), "offX": offset.x, "offY": offset.y, "offZ": offset.z}))).await.map(|_| ())
     }
 
     /// Changes the map.
@@ -631,6 +1023,73 @@ impl Omegga {
             .map(|_| ())
     }
 
+    /// Get every minigame on the server.
+    pub async fn get_minigames(&self) -> Result<Vec<Minigame>, ResponseError> {
+        self.request("getMinigames", None).await.map(|r| match r {
+            Some(r) => serde_json::from_value::<Vec<Minigame>>(r).unwrap_or_else(|_| vec![]),
+            None => vec![],
+        })
+    }
+
+    /// Get the names of the server's minigame presets.
+    pub async fn get_minigame_presets(&self) -> Result<Vec<String>, ResponseError> {
+        self.request("getMinigamePresets", None)
+            .await
+            .map(|r| match r {
+                Some(r) => serde_json::from_value::<Vec<String>>(r).unwrap_or_else(|_| vec![]),
+                None => vec![],
+            })
+    }
+
+    /// Reset a minigame, by its index.
+    pub async fn reset_minigame(&self, index: u32) -> Result<(), ResponseError> {
+        self.request("resetMinigame", Some(json!(index)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Start the next round of a minigame, by its index.
+    pub async fn next_round_minigame(&self, index: u32) -> Result<(), ResponseError> {
+        self.request("nextRoundMinigame", Some(json!(index)))
+            .await
+            .map(|_| ())
+    }
+
+    /// Load a minigame preset, optionally owned by a player, by their ID.
+    pub async fn load_minigame(
+        &self,
+        preset: impl Into<String>,
+        owner: Option<String>,
+    ) -> Result<(), ResponseError> {
+        self.request(
+            "loadMinigame",
+            Some(json!({"saveName": preset.into(), "owner": owner})),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Save a minigame, by its index, to a named preset.
+    pub async fn save_minigame(
+        &self,
+        index: u32,
+        preset: impl Into<String>,
+    ) -> Result<(), ResponseError> {
+        self.request(
+            "saveMinigame",
+            Some(json!({"index": index, "name": preset.into()})),
+        )
+        .await
+        .map(|_| ())
+    }
+
+    /// Delete a minigame, by its index.
+    pub async fn delete_minigame(&self, index: u32) -> Result<(), ResponseError> {
+        self.request("deleteMinigame", Some(json!(index)))
+            .await
+            .map(|_| ())
+    }
+
     /// Get a player.
     pub async fn get_player(
         &self,
@@ -655,10 +1114,10 @@ impl Omegga {
     pub async fn get_player_permissions(
         &self,
         target: impl Into<String>,
-    ) -> Result<Value, ResponseError> {
+    ) -> Result<Option<Permissions>, ResponseError> {
         self.request("player.getPermissions", Some(Value::String(target.into())))
             .await
-            .map(|r| r.unwrap_or(Value::Null))
+            .map(|r| r.and_then(|r| serde_json::from_value::<_>(r).ok()))
     }
 
     /// Get a player's name color (6-digit hexadecimal).
@@ -675,11 +1134,11 @@ impl Omegga {
     pub async fn get_player_position(
         &self,
         target: impl Into<String>,
-    ) -> Result<Option<(f64, f64, f64)>, ResponseError> {
+    ) -> Result<Option<Vec3>, ResponseError> {
         self.request("player.getPosition", Some(Value::String(target.into())))
             .await
             .map(|r| match r {
-                Some(r) => serde_json::from_value::<(f64, f64, f64)>(r).ok(),
+                Some(r) => serde_json::from_value::<Vec3>(r).ok(),
                 None => None,
             })
     }
@@ -750,11 +1209,12 @@ impl Omegga {
         &self,
         target: impl Into<String>,
         data: Value,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
         rotate: bool,
         quiet: bool,
     ) -> Result<(), ResponseError> {
-        self.request("player.loadDataAtGhostBrick", Some(json!({"target": target.into(), "data": data, "offX": offset.0, "offY": offset.1, "offZ": offset.2, "rotate": rotate, "quiet": quiet})))
+        let offset = offset.into();
+        self.request("player.loadDataAtGhostBrick", Some(json!({"target": target.into(), "data": data, "offX": offset.x, "offY": offset.y, "offZ": offset.z, "rotate": rotate, "quiet": quiet})))
             .await
             .map(|_| ())
     }
@@ -765,15 +1225,36 @@ impl Omegga {
         &self,
         target: impl Into<String>,
         data: save::SaveData,
-        offset: (i32, i32, i32),
+        offset: impl Into<IVec3>,
         rotate: bool,
         quiet: bool,
     ) -> Result<(), ResponseError> {
-        self.request("player.loadDataAtGhostBrick", Some(json!({"target": target.into(), "data": data, "offX": offset.0, "offY": offset.1, "offZ": offset.2, "rotate": rotate, "quiet": quiet})))
+        let offset = offset.into();
+        self.request("player.loadDataAtGhostBrick", Some(json!({"target": target.into(), "data": data, "offX": offset.x, "offY": offset.y, "offZ": offset.z, "rotate": rotate, "quiet": quiet})))
             .await
             .map(|_| ())
     }
 
+    /// Place brickadia-rs save data where a player's ghost brick is, centered on the
+    /// ghost brick and rotated to match its orientation.
+    #[cfg(feature = "brs")]
+    pub async fn paste_at_ghost_brick(
+        &self,
+        target: impl Into<String>,
+        mut data: save::SaveData,
+        quiet: bool,
+    ) -> Result<bool, ResponseError> {
+        let ghost = match self.get_player_ghost_brick(target).await? {
+            Some(ghost) => ghost,
+            None => return Ok(false),
+        };
+
+        orientation::place_save(&mut data, ghost.location.round(), ghost.orientation);
+        self.load_save_data(data, quiet, IVec3::ZERO)
+            .await
+            .map(|_| true)
+    }
+
     /// Get a plugin.
     pub async fn get_plugin(
         &self,
@@ -794,8 +1275,8 @@ impl Omegga {
     where
         T: serde::de::DeserializeOwned,
     {
-        let mut query = vec![Value::String(target.into()), Value::String(event.into())];
-        query.extend(args.into_iter());
+        let mut query = vec![Value::String(target), Value::String(event)];
+        query.extend(args);
 
         self.request("plugin.emit", Some(Value::Array(query)))
             .await
@@ -812,3 +1293,229 @@ impl Default for Omegga {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(method: &str, params: &str) -> String {
+        format!(
+            r#"{{"jsonrpc":"2.0","method":"{}","params":{}}}"#,
+            method, params
+        )
+    }
+
+    #[test]
+    fn ignores_invalid_lines() {
+        let omegga = Omegga::new();
+        assert!(omegga.handle_line("").is_none());
+        assert!(omegga.handle_line("not json").is_none());
+        assert!(omegga.handle_line(r#"{"jsonrpc":"2.0"}"#).is_none());
+    }
+
+    #[test]
+    fn ignores_malformed_params() {
+        let omegga = Omegga::new();
+        let lines = [
+            r#"{"jsonrpc":"2.0","method":"line"}"#.to_string(),
+            notification("line", "[]"),
+            notification("line", "[1]"),
+            r#"{"jsonrpc":"2.0","method":"chat"}"#.to_string(),
+            notification("chat", r#"["x"]"#),
+            r#"{"jsonrpc":"2.0","method":"join"}"#.to_string(),
+            notification("join", "[]"),
+            notification("leave", r#"[{"name":"x"}]"#),
+            notification("start", "[{}]"),
+            notification("mapchange", "null"),
+            notification("host", "[]"),
+            notification("cmd:test", "[]"),
+            notification("chatcmd:test", "[1, 2]"),
+            notification("interact", "[]"),
+            notification("event:test", "[]"),
+            notification("event:test", r#"[{"name":"x","id":"y"}, 1]"#),
+            r#"{"jsonrpc":"2.0","id":1,"method":"plugin:emit","params":[]}"#.to_string(),
+            r#"{"jsonrpc":"2.0","id":1,"method":"plugin:emit","params":["event"]}"#.to_string(),
+        ];
+        for line in lines {
+            assert!(omegga.handle_line(&line).is_none(), "{}", line);
+        }
+    }
+
+    #[test]
+    fn updates_server_info_from_bootstrap() {
+        let omegga = Omegga::new();
+        let event = omegga.handle_line(&notification(
+            "bootstrap",
+            r#"[{"host":{"name":"x","id":"i"},"version":7870,"online":[["x","i","c","s"]],"path":"/server"}]"#,
+        ));
+        match event {
+            Some(Event::Bootstrap(bootstrap)) => assert_eq!(bootstrap.players[0].name, "x"),
+            e => panic!("expected a bootstrap event, got {:?}", e),
+        }
+
+        let info = omegga.server_info();
+        assert_eq!(info.host.map(|h| h.id).as_deref(), Some("i"));
+        assert_eq!(info.version, Some(7870));
+        assert_eq!(info.path.as_deref(), Some("/server"));
+
+        // a malformed bootstrap is ignored rather than clearing what's known
+        assert!(omegga
+            .handle_line(&notification("bootstrap", r#"[{"online":"x"}]"#))
+            .is_none());
+        assert_eq!(omegga.server_info().version, Some(7870));
+    }
+
+    #[test]
+    fn caps_long_middle_prints() {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+        let printed = |omegga: &Omegga| match omegga.replay_output().pop() {
+            Some(rpc::Message::Notification {
+                params: Some(params),
+                ..
+            }) => params["line"].as_str().unwrap().to_string(),
+            m => panic!("expected a middle print, got {:?}", m),
+        };
+
+        omegga.middle_print_long("x", "hello there");
+        assert_eq!(printed(&omegga), "hello there");
+
+        let word = "a".repeat(chat::MAX_LINE_LENGTH - 10);
+        omegga.middle_print_long("x", [word.as_str(); 2].join(" "));
+        assert_eq!(printed(&omegga), format!("{}<br>{}", word, word));
+
+        let text = [word.as_str(); 10].join(" ");
+        omegga.middle_print_long("x", &text);
+        let print = printed(&omegga);
+        assert!(print.len() <= chat::MAX_MIDDLE_PRINT_LENGTH);
+        assert_eq!(print, format!("{}...", [word.as_str(); 4].join("<br>")));
+    }
+
+    #[test]
+    fn updates_version_only_when_known() {
+        let omegga = Omegga::new();
+        assert!(matches!(
+            omegga.handle_line(&notification("version", "[7870]")),
+            Some(Event::Version(VersionInfo {
+                changelist: Some(7870),
+                ..
+            }))
+        ));
+        assert_eq!(omegga.server_info().version, Some(7870));
+
+        assert!(matches!(
+            omegga.handle_line(&notification("version", r#"["unknown"]"#)),
+            Some(Event::Version(VersionInfo {
+                changelist: None,
+                ..
+            }))
+        ));
+        assert_eq!(omegga.server_info().version, Some(7870));
+
+        omegga.handle_line(&notification("version", r#"["7900"]"#));
+        assert_eq!(omegga.server_info().version, Some(7900));
+    }
+
+    #[test]
+    fn decodes_well_formed_params() {
+        let omegga = Omegga::new();
+        assert!(matches!(
+            omegga.handle_line(&notification("line", r#"["hello"]"#)),
+            Some(Event::Line(line)) if line == "hello"
+        ));
+        assert!(matches!(
+            omegga.handle_line(&notification("chat", r#"["x", "hi"]"#)),
+            Some(Event::Chat { player, message }) if player == "x" && message == "hi"
+        ));
+        assert!(matches!(
+            omegga.handle_line(&notification(
+                "event:test",
+                r#"[{"name":"x","id":"y","controller":"c","state":"s"}, "a"]"#
+            )),
+            Some(Event::Event { name, args, .. }) if name == "test" && args == ["a"]
+        ));
+        assert!(matches!(
+            omegga.handle_line(r#"{"jsonrpc":"2.0","id":1,"method":"plugin:emit","params":["e","p",1]}"#),
+            Some(Event::PluginEmit { event, from, args, .. })
+                if event == "e" && from == "p" && args.len() == 1
+        ));
+    }
+
+    /// Feed a console line to the plugin, as Omegga would.
+    #[cfg(feature = "tokio")]
+    fn console_line(omegga: &Omegga, line: &str) {
+        let params = serde_json::to_string(&[line]).unwrap();
+        assert!(omegga.handle_line(&notification("line", &params)).is_some());
+    }
+
+    /// Start a capture, returning once its command has been written.
+    #[cfg(feature = "tokio")]
+    async fn start_capture(
+        omegga: &Omegga,
+        capture: Capture,
+    ) -> JoinHandle<Result<Vec<String>, CaptureTimeout>> {
+        let task = tokio::spawn({
+            let omegga = omegga.clone();
+            async move {
+                omegga
+                    .exec_and_capture("Server.Status", capture, Duration::from_secs(1))
+                    .await
+            }
+        });
+        while !omegga.replay_output().iter().any(|m| {
+            matches!(m, rpc::Message::Notification { method, params, .. }
+                if method == "exec" && params.as_ref() == Some(&json!("Server.Status")))
+        }) {
+            tokio::task::yield_now().await;
+        }
+        task
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn captures_matching_lines_until_the_timeout() {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+        console_line(&omegga, "[2021.09.24-21.13.45:123][  0]LogServer: before");
+
+        let capture = start_capture(&omegga, Capture::matching("^LogServer: ").unwrap()).await;
+        console_line(&omegga, "[2021.09.24-21.13.46:123][  1]LogServer: one");
+        console_line(&omegga, "[2021.09.24-21.13.46:123][  1]LogChat: x: hi");
+        time::advance(Duration::from_millis(900)).await;
+        console_line(&omegga, "[2021.09.24-21.13.47:123][  2]LogServer: two");
+        let lines = capture.await.unwrap().unwrap();
+        assert_eq!(
+            lines,
+            [
+                "[2021.09.24-21.13.46:123][  1]LogServer: one",
+                "[2021.09.24-21.13.47:123][  2]LogServer: two",
+            ]
+        );
+
+        // lines after the capture ends aren't kept for the next one
+        console_line(&omegga, "[2021.09.24-21.13.48:123][  3]LogServer: after");
+        let capture = start_capture(&omegga, Capture::matching("^LogServer: ").unwrap()).await;
+        assert!(capture.await.unwrap().unwrap().is_empty());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn captures_until_a_terminator() {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+
+        let capture = start_capture(&omegga, Capture::until("^LogServer: done$").unwrap()).await;
+        console_line(&omegga, "LogServer: one");
+        console_line(&omegga, "LogServer: done");
+        console_line(&omegga, "LogServer: after");
+        assert_eq!(
+            capture.await.unwrap().unwrap(),
+            ["LogServer: one", "LogServer: done"]
+        );
+
+        let capture = start_capture(&omegga, Capture::until("^LogServer: done$").unwrap()).await;
+        console_line(&omegga, "LogServer: one");
+        let err = capture.await.unwrap().unwrap_err();
+        assert_eq!(err.lines, ["LogServer: one"]);
+    }
+}
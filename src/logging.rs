@@ -0,0 +1,82 @@
+use std::{
+    fmt::Write as _,
+    io::{self, Write},
+};
+
+use log::{
+    kv::{self, VisitSource},
+    Level, LevelFilter, Log, Metadata, Record, SetLoggerError,
+};
+
+use crate::Omegga;
+
+/// A [`log`] logger that forwards records to the Omegga console, so the usual logging
+/// macros (`info!`, `warn!`, ...) show up there.
+///
+/// Errors, warnings and info go to [`Omegga::error`], [`Omegga::warn`] and [`Omegga::info`],
+/// debug records go to [`Omegga::log`], and trace records go to [`Omegga::trace`].
+/// Records are formatted like `[target] message key=value`.
+///
+/// Crates using `tracing` can forward to this logger with `tracing`'s `log` feature.
+pub struct OmeggaLogger {
+    omegga: Omegga,
+    level: LevelFilter,
+}
+
+impl OmeggaLogger {
+    /// Create a logger that forwards records at `Info` or above.
+    pub fn new(omegga: &Omegga) -> Self {
+        Self {
+            omegga: omegga.clone(),
+            level: LevelFilter::Info,
+        }
+    }
+
+    /// Set the most verbose level forwarded.
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.level = level;
+        self
+    }
+
+    /// Install this as the global logger. This fails if a logger is already installed.
+    pub fn init(self) -> Result<(), SetLoggerError> {
+        log::set_max_level(self.level);
+        log::set_boxed_logger(Box::new(self))
+    }
+}
+
+struct KeyValues<'a>(&'a mut String);
+
+impl<'kvs> VisitSource<'kvs> for KeyValues<'_> {
+    fn visit_pair(&mut self, key: kv::Key<'kvs>, value: kv::Value<'kvs>) -> Result<(), kv::Error> {
+        let _ = write!(self.0, " {}={}", key, value);
+        Ok(())
+    }
+}
+
+impl Log for OmeggaLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let mut line = format!("[{}] {}", record.target(), record.args());
+        let _ = record.key_values().visit(&mut KeyValues(&mut line));
+
+        match record.level() {
+            Level::Error => self.omegga.error(line),
+            Level::Warn => self.omegga.warn(line),
+            Level::Info => self.omegga.info(line),
+            Level::Debug => self.omegga.log(line),
+            Level::Trace => self.omegga.trace(line),
+        }
+    }
+
+    fn flush(&self) {
+        let _ = io::stdout().flush();
+    }
+}
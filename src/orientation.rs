@@ -0,0 +1,540 @@
+use std::{fmt, str::FromStr};
+
+#[cfg(feature = "brs")]
+use brickadia::save;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::geometry::IVec3;
+
+/// A 3x3 rotation matrix, in rows.
+type Matrix = [[i32; 3]; 3];
+
+const IDENTITY: Matrix = [[1, 0, 0], [0, 1, 0], [0, 0, 1]];
+
+fn mul(a: &Matrix, b: &Matrix) -> Matrix {
+    let mut m = [[0; 3]; 3];
+    for (i, row) in m.iter_mut().enumerate() {
+        for (j, cell) in row.iter_mut().enumerate() {
+            *cell = (0..3).map(|k| a[i][k] * b[k][j]).sum();
+        }
+    }
+    m
+}
+
+/// The axis a brick's top faces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    XPositive,
+    XNegative,
+    YPositive,
+    YNegative,
+    ZPositive,
+    ZNegative,
+}
+
+impl Direction {
+    pub const ALL: [Direction; 6] = [
+        Direction::XPositive,
+        Direction::XNegative,
+        Direction::YPositive,
+        Direction::YNegative,
+        Direction::ZPositive,
+        Direction::ZNegative,
+    ];
+
+    /// The rotation taking `ZPositive` to this direction.
+    fn matrix(self) -> Matrix {
+        match self {
+            Direction::XPositive => [[0, 0, 1], [0, 1, 0], [-1, 0, 0]],
+            Direction::XNegative => [[0, 0, -1], [0, 1, 0], [1, 0, 0]],
+            Direction::YPositive => [[1, 0, 0], [0, 0, 1], [0, -1, 0]],
+            Direction::YNegative => [[1, 0, 0], [0, 0, -1], [0, 1, 0]],
+            Direction::ZPositive => IDENTITY,
+            Direction::ZNegative => [[1, 0, 0], [0, -1, 0], [0, 0, -1]],
+        }
+    }
+}
+
+/// A brick's rotation about the axis it faces, in 90 degree steps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Rotation {
+    Deg0,
+    Deg90,
+    Deg180,
+    Deg270,
+}
+
+impl Rotation {
+    pub const ALL: [Rotation; 4] = [
+        Rotation::Deg0,
+        Rotation::Deg90,
+        Rotation::Deg180,
+        Rotation::Deg270,
+    ];
+
+    /// The rotation in degrees.
+    pub fn degrees(self) -> i32 {
+        self as i32 * 90
+    }
+
+    /// The rotation about the Z axis.
+    fn matrix(self) -> Matrix {
+        match self {
+            Rotation::Deg0 => IDENTITY,
+            Rotation::Deg90 => [[0, -1, 0], [1, 0, 0], [0, 0, 1]],
+            Rotation::Deg180 => [[-1, 0, 0], [0, -1, 0], [0, 0, 1]],
+            Rotation::Deg270 => [[0, 1, 0], [-1, 0, 0], [0, 0, 1]],
+        }
+    }
+}
+
+/// An orientation, made of a direction and a rotation, as used by bricks and ghost bricks.
+///
+/// Parses from and serializes to Omegga's format, like `Z_Positive_0` or `X_Negative_270`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Orientation {
+    pub direction: Direction,
+    pub rotation: Rotation,
+}
+
+impl Orientation {
+    /// The default orientation, facing up with no rotation.
+    pub const UP: Orientation = Orientation::new(Direction::ZPositive, Rotation::Deg0);
+
+    /// Create a new orientation.
+    pub const fn new(direction: Direction, rotation: Rotation) -> Self {
+        Self {
+            direction,
+            rotation,
+        }
+    }
+
+    fn matrix(&self) -> Matrix {
+        mul(&self.direction.matrix(), &self.rotation.matrix())
+    }
+
+    fn from_matrix(m: &Matrix) -> Self {
+        Direction::ALL
+            .iter()
+            .flat_map(|&d| Rotation::ALL.iter().map(move |&r| Orientation::new(d, r)))
+            .find(|o| &o.matrix() == m)
+            .expect("every rotation matrix has an orientation")
+    }
+
+    /// Rotate a vector by this orientation.
+    pub fn rotate(&self, v: IVec3) -> IVec3 {
+        let m = self.matrix();
+        IVec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+
+    /// The orientation of something with orientation `other`, after being rotated by this
+    /// orientation.
+    pub fn apply(&self, other: Orientation) -> Orientation {
+        Orientation::from_matrix(&mul(&self.matrix(), &other.matrix()))
+    }
+
+    /// The orientation that undoes this orientation.
+    pub fn inverse(&self) -> Orientation {
+        let m = self.matrix();
+        let mut t = [[0; 3]; 3];
+        for (i, row) in t.iter_mut().enumerate() {
+            for (j, cell) in row.iter_mut().enumerate() {
+                *cell = m[j][i];
+            }
+        }
+        Orientation::from_matrix(&t)
+    }
+}
+
+impl Default for Orientation {
+    fn default() -> Self {
+        Orientation::UP
+    }
+}
+
+/// An error parsing an [`Orientation`].
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid orientation: {0}")]
+pub struct ParseOrientationError(String);
+
+impl FromStr for Orientation {
+    type Err = ParseOrientationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseOrientationError(s.to_string());
+        let mut parts = s.split('_');
+
+        let direction = match (parts.next(), parts.next()) {
+            (Some("X"), Some("Positive")) => Direction::XPositive,
+            (Some("X"), Some("Negative")) => Direction::XNegative,
+            (Some("Y"), Some("Positive")) => Direction::YPositive,
+            (Some("Y"), Some("Negative")) => Direction::YNegative,
+            (Some("Z"), Some("Positive")) => Direction::ZPositive,
+            (Some("Z"), Some("Negative")) => Direction::ZNegative,
+            _ => return Err(err()),
+        };
+        let rotation = match parts.next() {
+            Some("0") => Rotation::Deg0,
+            Some("90") => Rotation::Deg90,
+            Some("180") => Rotation::Deg180,
+            Some("270") => Rotation::Deg270,
+            _ => return Err(err()),
+        };
+
+        match parts.next() {
+            None => Ok(Orientation::new(direction, rotation)),
+            Some(_) => Err(err()),
+        }
+    }
+}
+
+impl TryFrom<String> for Orientation {
+    type Error = ParseOrientationError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl fmt::Display for Orientation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (axis, sign) = match self.direction {
+            Direction::XPositive => ("X", "Positive"),
+            Direction::XNegative => ("X", "Negative"),
+            Direction::YPositive => ("Y", "Positive"),
+            Direction::YNegative => ("Y", "Negative"),
+            Direction::ZPositive => ("Z", "Positive"),
+            Direction::ZNegative => ("Z", "Negative"),
+        };
+        write!(f, "{}_{}_{}", axis, sign, self.rotation.degrees())
+    }
+}
+
+impl From<Orientation> for String {
+    fn from(value: Orientation) -> Self {
+        value.to_string()
+    }
+}
+
+#[cfg(feature = "brs")]
+impl From<&save::Direction> for Direction {
+    fn from(value: &save::Direction) -> Self {
+        match value {
+            save::Direction::XPositive => Direction::XPositive,
+            save::Direction::XNegative => Direction::XNegative,
+            save::Direction::YPositive => Direction::YPositive,
+            save::Direction::YNegative => Direction::YNegative,
+            save::Direction::ZPositive => Direction::ZPositive,
+            save::Direction::ZNegative => Direction::ZNegative,
+        }
+    }
+}
+
+#[cfg(feature = "brs")]
+impl From<Direction> for save::Direction {
+    fn from(value: Direction) -> Self {
+        match value {
+            Direction::XPositive => save::Direction::XPositive,
+            Direction::XNegative => save::Direction::XNegative,
+            Direction::YPositive => save::Direction::YPositive,
+            Direction::YNegative => save::Direction::YNegative,
+            Direction::ZPositive => save::Direction::ZPositive,
+            Direction::ZNegative => save::Direction::ZNegative,
+        }
+    }
+}
+
+#[cfg(feature = "brs")]
+impl From<&save::Rotation> for Rotation {
+    fn from(value: &save::Rotation) -> Self {
+        match value {
+            save::Rotation::Deg0 => Rotation::Deg0,
+            save::Rotation::Deg90 => Rotation::Deg90,
+            save::Rotation::Deg180 => Rotation::Deg180,
+            save::Rotation::Deg270 => Rotation::Deg270,
+        }
+    }
+}
+
+#[cfg(feature = "brs")]
+impl From<Rotation> for save::Rotation {
+    fn from(value: Rotation) -> Self {
+        match value {
+            Rotation::Deg0 => save::Rotation::Deg0,
+            Rotation::Deg90 => save::Rotation::Deg90,
+            Rotation::Deg180 => save::Rotation::Deg180,
+            Rotation::Deg270 => save::Rotation::Deg270,
+        }
+    }
+}
+
+/// The bounds of the bricks in a save, as the minimum and maximum corners.
+#[cfg(feature = "brs")]
+pub fn save_bounds(data: &save::SaveData) -> Option<(IVec3, IVec3)> {
+    data.bricks
+        .iter()
+        .map(|brick| {
+            let position = IVec3::from(brick.position);
+            let size = match brick.size {
+                save::Size::Procedural(x, y, z) => {
+                    let orientation = Orientation::new(
+                        Direction::from(&brick.direction),
+                        Rotation::from(&brick.rotation),
+                    );
+                    let size = orientation.rotate(IVec3::new(x as i32, y as i32, z as i32));
+                    IVec3::new(size.x.abs(), size.y.abs(), size.z.abs())
+                }
+                save::Size::Empty => IVec3::ZERO,
+            };
+            (position - size, position + size)
+        })
+        .reduce(|(min_a, max_a), (min_b, max_b)| (min_a.min(min_b), max_a.max(max_b)))
+}
+
+/// Rotate every brick in a save about the origin.
+#[cfg(feature = "brs")]
+pub fn rotate_save(data: &mut save::SaveData, orientation: Orientation) {
+    for brick in data.bricks.iter_mut() {
+        brick.position = orientation.rotate(brick.position.into()).into();
+
+        let rotated = orientation.apply(Orientation::new(
+            Direction::from(&brick.direction),
+            Rotation::from(&brick.rotation),
+        ));
+        brick.direction = rotated.direction.into();
+        brick.rotation = rotated.rotation.into();
+    }
+}
+
+/// Move every brick in a save by an offset.
+#[cfg(feature = "brs")]
+pub fn translate_save(data: &mut save::SaveData, offset: impl Into<IVec3>) {
+    let offset = offset.into();
+    for brick in data.bricks.iter_mut() {
+        brick.position = (IVec3::from(brick.position) + offset).into();
+    }
+}
+
+/// Center a save on a position, then rotate it about that position.
+/// Use with a [`GhostBrick`](crate::resources::GhostBrick)'s location and orientation
+/// to place a save where a player is looking.
+#[cfg(feature = "brs")]
+pub fn place_save(data: &mut save::SaveData, position: impl Into<IVec3>, orientation: Orientation) {
+    if let Some((min, max)) = save_bounds(data) {
+        translate_save(data, -((min + max) / 2));
+    }
+    rotate_save(data, orientation);
+    translate_save(data, position);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn all() -> impl Iterator<Item = Orientation> {
+        Direction::ALL
+            .iter()
+            .flat_map(|&d| Rotation::ALL.iter().map(move |&r| Orientation::new(d, r)))
+    }
+
+    /// Omegga's brick utilities orient a position by applying the rotation, then the
+    /// direction, as these tables.
+    fn omegga_rotate(orientation: Orientation, v: IVec3) -> IVec3 {
+        let IVec3 { x, y, z } = v;
+        let (x, y, z) = match orientation.rotation {
+            Rotation::Deg0 => (x, y, z),
+            Rotation::Deg90 => (-y, x, z),
+            Rotation::Deg180 => (-x, -y, z),
+            Rotation::Deg270 => (y, -x, z),
+        };
+        let (x, y, z) = match orientation.direction {
+            Direction::XPositive => (z, y, -x),
+            Direction::XNegative => (-z, y, x),
+            Direction::YPositive => (x, z, -y),
+            Direction::YNegative => (x, -z, y),
+            Direction::ZPositive => (x, y, z),
+            Direction::ZNegative => (x, -y, -z),
+        };
+        IVec3::new(x, y, z)
+    }
+
+    fn det(m: &Matrix) -> i32 {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    #[test]
+    fn round_trips_every_orientation() {
+        let strings = all().map(|o| o.to_string()).collect::<Vec<_>>();
+        assert_eq!(strings.len(), 24);
+        for (o, s) in all().zip(&strings) {
+            assert_eq!(s.parse::<Orientation>(), Ok(o));
+            let json = serde_json::to_value(o).unwrap();
+            assert_eq!(json, serde_json::Value::String(s.clone()));
+            assert_eq!(serde_json::from_value::<Orientation>(json).unwrap(), o);
+        }
+        assert!(strings.contains(&"Z_Positive_0".to_string()));
+        assert!(strings.contains(&"X_Negative_270".to_string()));
+
+        // every orientation is a different rotation
+        for (i, a) in all().enumerate() {
+            for b in all().skip(i + 1) {
+                assert_ne!(a.matrix(), b.matrix(), "{} and {}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_orientations() {
+        for s in [
+            "",
+            "Z",
+            "Z_Positive",
+            "Z_Positive_45",
+            "W_Positive_0",
+            "Z_Positive_0_0",
+        ] {
+            assert!(s.parse::<Orientation>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn matches_omegga_orientation_tables() {
+        let v = IVec3::new(1, 2, 3);
+        for o in all() {
+            assert_eq!(o.rotate(v), omegga_rotate(o, v), "{}", o);
+        }
+    }
+
+    #[test]
+    fn faces_top_along_direction() {
+        let up = IVec3::new(0, 0, 1);
+        let faces = [
+            (Direction::XPositive, IVec3::new(1, 0, 0)),
+            (Direction::XNegative, IVec3::new(-1, 0, 0)),
+            (Direction::YPositive, IVec3::new(0, 1, 0)),
+            (Direction::YNegative, IVec3::new(0, -1, 0)),
+            (Direction::ZPositive, IVec3::new(0, 0, 1)),
+            (Direction::ZNegative, IVec3::new(0, 0, -1)),
+        ];
+        for (direction, face) in faces {
+            for rotation in Rotation::ALL {
+                assert_eq!(Orientation::new(direction, rotation).rotate(up), face);
+            }
+        }
+    }
+
+    #[test]
+    fn never_mirrors() {
+        // mirroring would flip the handedness of the axes, turning saves inside out
+        for o in all() {
+            assert_eq!(det(&o.matrix()), 1, "{}", o);
+        }
+    }
+
+    #[test]
+    fn inverts_and_applies() {
+        let v = IVec3::new(4, -5, 6);
+        for a in all() {
+            assert_eq!(a.apply(a.inverse()), Orientation::UP, "{}", a);
+            assert_eq!(a.inverse().apply(a), Orientation::UP, "{}", a);
+            assert_eq!(a.inverse().rotate(a.rotate(v)), v, "{}", a);
+            assert_eq!(Orientation::UP.apply(a), a);
+            assert_eq!(a.apply(Orientation::UP), a);
+
+            for b in all() {
+                assert_eq!(a.apply(b).rotate(v), a.rotate(b.rotate(v)), "{} {}", a, b);
+            }
+        }
+    }
+
+    #[cfg(feature = "brs")]
+    fn brick(position: (i32, i32, i32), size: (u32, u32, u32), orientation: &str) -> save::Brick {
+        let orientation = orientation.parse::<Orientation>().unwrap();
+        save::Brick {
+            position,
+            size: save::Size::Procedural(size.0, size.1, size.2),
+            direction: orientation.direction.into(),
+            rotation: orientation.rotation.into(),
+            ..Default::default()
+        }
+    }
+
+    #[cfg(feature = "brs")]
+    fn orientation_of(brick: &save::Brick) -> String {
+        Orientation::new(
+            Direction::from(&brick.direction),
+            Rotation::from(&brick.rotation),
+        )
+        .to_string()
+    }
+
+    #[cfg(feature = "brs")]
+    #[test]
+    fn rotates_save() {
+        let mut data = save::SaveData {
+            bricks: vec![
+                brick((10, 0, 0), (5, 5, 2), "Z_Positive_0"),
+                brick((0, 20, 6), (5, 10, 2), "X_Positive_90"),
+            ],
+            ..Default::default()
+        };
+
+        rotate_save(&mut data, "X_Positive_0".parse().unwrap());
+        assert_eq!(data.bricks[0].position, (0, 0, -10));
+        assert_eq!(orientation_of(&data.bricks[0]), "X_Positive_0");
+        assert_eq!(data.bricks[1].position, (6, 20, 0));
+
+        rotate_save(&mut data, "Z_Positive_90".parse().unwrap());
+        assert_eq!(data.bricks[0].position, (0, 0, -10));
+        assert_eq!(orientation_of(&data.bricks[0]), "Y_Positive_90");
+        assert_eq!(data.bricks[1].position, (-20, 6, 0));
+
+        // rotating back restores every brick
+        let undo = "X_Positive_0"
+            .parse::<Orientation>()
+            .unwrap()
+            .inverse()
+            .apply("Z_Positive_90".parse::<Orientation>().unwrap().inverse());
+        rotate_save(&mut data, undo);
+        assert_eq!(data.bricks[0].position, (10, 0, 0));
+        assert_eq!(orientation_of(&data.bricks[0]), "Z_Positive_0");
+        assert_eq!(data.bricks[1].position, (0, 20, 6));
+        assert_eq!(orientation_of(&data.bricks[1]), "X_Positive_90");
+    }
+
+    #[cfg(feature = "brs")]
+    #[test]
+    fn places_save() {
+        let mut data = save::SaveData {
+            bricks: vec![
+                brick((5, 5, 2), (5, 5, 2), "Z_Positive_0"),
+                brick((25, 5, 2), (5, 5, 2), "Z_Positive_0"),
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            save_bounds(&data),
+            Some((IVec3::new(0, 0, 0), IVec3::new(30, 10, 4)))
+        );
+
+        // facing a wall: the save's top faces +Y, centered on the ghost brick
+        place_save(&mut data, (100, 200, 300), "Y_Positive_0".parse().unwrap());
+        assert_eq!(data.bricks[0].position, (90, 200, 300));
+        assert_eq!(data.bricks[1].position, (110, 200, 300));
+        for brick in &data.bricks {
+            assert_eq!(orientation_of(brick), "Y_Positive_0");
+        }
+        assert_eq!(
+            save_bounds(&data),
+            Some((IVec3::new(85, 198, 295), IVec3::new(115, 202, 305)))
+        );
+    }
+}
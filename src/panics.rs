@@ -0,0 +1,216 @@
+use std::{
+    any::Any,
+    backtrace::Backtrace,
+    io::{self, Write},
+    panic::{self, PanicHookInfo},
+};
+#[cfg(feature = "tokio")]
+use std::{future::Future, time::Duration};
+
+#[cfg(feature = "tokio")]
+use tokio::{task::JoinHandle, time};
+
+use serde_json::Value;
+
+use crate::{rpc, Omegga};
+
+fn payload_message(payload: &(dyn Any + Send)) -> &str {
+    match payload.downcast_ref::<&str>() {
+        Some(s) => s,
+        None => match payload.downcast_ref::<String>() {
+            Some(s) => s.as_str(),
+            None => "Box<dyn Any>",
+        },
+    }
+}
+
+/// Install a panic hook that reports panics, with their location and a backtrace,
+/// to the Omegga console in error color before running the previous hook.
+///
+/// Without this, Omegga only sees the plugin exit when its main task panics. A panic
+/// while a session is being recorded or replayed is left out of the session if the
+/// panicking thread was using it.
+pub fn install_hook(omegga: &Omegga) {
+    let omegga = omegga.clone();
+    let previous = panic::take_hook();
+    panic::set_hook(Box::new(move |info: &PanicHookInfo| {
+        let thread = std::thread::current();
+        let location = info
+            .location()
+            .map(|l| format!(" at {}:{}:{}", l.file(), l.line(), l.column()))
+            .unwrap_or_default();
+        let message = format!(
+            "thread '{}' panicked{}: {}\n{}",
+            thread.name().unwrap_or("<unnamed>"),
+            location,
+            payload_message(info.payload()),
+            Backtrace::force_capture()
+        );
+        omegga.write_from_panic_hook(rpc::Message::notification(
+            "error".to_string(),
+            Some(Value::String(message)),
+        ));
+        let _ = io::stdout().flush();
+        previous(info);
+    }));
+}
+
+/// How [`supervise`] restarts a task that panics.
+#[cfg(feature = "tokio")]
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// How many times to restart the task before giving up, or `None` to always restart.
+    pub max_restarts: Option<usize>,
+    /// How long to wait before restarting the task.
+    pub delay: Duration,
+}
+
+#[cfg(feature = "tokio")]
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_restarts: Some(5),
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// Spawn a task, restarting it when it panics instead of letting it die, as long as
+/// `policy` allows. Each restart is reported through [`Omegga::warn`].
+///
+/// The returned handle finishes when the task returns normally or gives up.
+#[cfg(feature = "tokio")]
+pub fn supervise<F, Fut>(
+    omegga: &Omegga,
+    name: impl Into<String>,
+    policy: RestartPolicy,
+    task: F,
+) -> JoinHandle<()>
+where
+    F: Fn() -> Fut + Send + Sync + 'static,
+    Fut: Future<Output = ()> + Send + 'static,
+{
+    let name = name.into();
+    let supervisor = omegga.clone();
+    omegga.spawn_task(async move {
+        let mut restarts = 0;
+        loop {
+            match tokio::spawn(task()).await {
+                Err(e) if e.is_panic() => {
+                    if policy.max_restarts.is_some_and(|max| restarts >= max) {
+                        supervisor.error(format!(
+                            "task '{}' panicked too many times, not restarting",
+                            name
+                        ));
+                        return;
+                    }
+
+                    restarts += 1;
+                    supervisor.warn(format!(
+                        "task '{}' panicked, restarting ({})",
+                        name, restarts
+                    ));
+                    time::sleep(policy.delay).await;
+                }
+                // the task finished or was cancelled
+                _ => return,
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_panic_payloads() {
+        assert_eq!(payload_message(&"static"), "static");
+        assert_eq!(payload_message(&String::from("formatted")), "formatted");
+        assert_eq!(payload_message(&42), "Box<dyn Any>");
+
+        let payload = std::panic::catch_unwind(|| panic!("at {}", 1)).unwrap_err();
+        assert_eq!(payload_message(payload.as_ref()), "at 1");
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod supervise_tests {
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+
+    use super::*;
+
+    /// The methods of the notifications written since this was last called.
+    fn written(omegga: &Omegga) -> Vec<String> {
+        omegga
+            .replay_output()
+            .into_iter()
+            .filter_map(|m| match m {
+                rpc::Message::Notification { method, .. } => Some(method),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn supervise_counting(
+        omegga: &Omegga,
+        panics: usize,
+        max_restarts: Option<usize>,
+    ) -> (JoinHandle<()>, Arc<AtomicUsize>) {
+        let runs = Arc::new(AtomicUsize::new(0));
+        let task_runs = Arc::clone(&runs);
+        let handle = supervise(
+            omegga,
+            "test",
+            RestartPolicy {
+                max_restarts,
+                delay: Duration::from_secs(1),
+            },
+            move || {
+                let run = task_runs.fetch_add(1, Ordering::SeqCst);
+                async move {
+                    if run < panics {
+                        panic!("run {}", run);
+                    }
+                }
+            },
+        );
+        (handle, runs)
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restarts_panicking_tasks() {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+
+        let (handle, runs) = supervise_counting(&omegga, 2, Some(5));
+        handle.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(written(&omegga), ["warn", "warn"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn gives_up_after_max_restarts() {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+
+        let (handle, runs) = supervise_counting(&omegga, usize::MAX, Some(2));
+        handle.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(written(&omegga), ["warn", "warn", "error"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn doesnt_restart_finished_tasks() {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+
+        let (handle, runs) = supervise_counting(&omegga, 0, None);
+        handle.await.unwrap();
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert!(written(&omegga).is_empty());
+    }
+}
@@ -0,0 +1,475 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    time::Duration,
+};
+
+use tokio::{
+    sync::mpsc::{self, UnboundedSender},
+    time::{self, Instant},
+};
+
+use crate::Omegga;
+
+/// A rate limit, allowing `messages` messages in any window of `per`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimit {
+    messages: usize,
+    per: Duration,
+}
+
+impl RateLimit {
+    /// Create a new rate limit. At least one message is always allowed per window.
+    pub fn new(messages: usize, per: Duration) -> Self {
+        Self {
+            messages: messages.max(1),
+            per,
+        }
+    }
+
+    /// The number of messages allowed in each window.
+    pub fn messages(&self) -> usize {
+        self.messages
+    }
+
+    /// The length of the window.
+    pub fn per(&self) -> Duration {
+        self.per
+    }
+}
+
+/// Configuration for a [`ChatQueue`].
+#[derive(Debug, Clone)]
+pub struct ChatQueueConfig {
+    /// The rate limit for all messages sent through the queue.
+    pub global: Option<RateLimit>,
+    /// The rate limit for messages sent to a single player, through
+    /// whispers and middle prints.
+    pub per_target: Option<RateLimit>,
+    /// If set, a message is dropped when it is identical to the last message
+    /// queued for the same target within this duration.
+    pub dedupe: Option<Duration>,
+}
+
+impl Default for ChatQueueConfig {
+    fn default() -> Self {
+        Self {
+            global: Some(RateLimit::new(5, Duration::from_secs(1))),
+            per_target: Some(RateLimit::new(3, Duration::from_secs(1))),
+            dedupe: Some(Duration::from_secs(5)),
+        }
+    }
+}
+
+/// The priority of a queued message. Higher priority messages are always sent first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    Low,
+    #[default]
+    Normal,
+    High,
+}
+
+/// An outbound chat message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChatMessage {
+    Broadcast(String),
+    Whisper { target: String, line: String },
+    MiddlePrint { target: String, line: String },
+}
+
+impl ChatMessage {
+    /// The player this message is sent to, if it isn't a broadcast.
+    pub fn target(&self) -> Option<&str> {
+        match self {
+            ChatMessage::Broadcast(_) => None,
+            ChatMessage::Whisper { target, .. } | ChatMessage::MiddlePrint { target, .. } => {
+                Some(target)
+            }
+        }
+    }
+
+    fn send(self, omegga: &Omegga) {
+        match self {
+            ChatMessage::Broadcast(line) => omegga.broadcast(line),
+            ChatMessage::Whisper { target, line } => omegga.whisper(target, line),
+            ChatMessage::MiddlePrint { target, line } => omegga.middle_print(target, line),
+        }
+    }
+}
+
+/// A rate-limited queue for outbound chat messages.
+///
+/// Messages are sent in priority order, and otherwise in the order they were queued.
+/// The queue is processed by a background task, which stops once every clone of the
+/// `ChatQueue` has been dropped and the remaining messages have been sent.
+#[derive(Debug, Clone)]
+pub struct ChatQueue {
+    tx: UnboundedSender<(Priority, ChatMessage)>,
+}
+
+impl ChatQueue {
+    /// Create a new chat queue and spawn its background task.
+    pub fn new(omegga: &Omegga, config: ChatQueueConfig) -> Self {
+        let (tx, rx) = mpsc::unbounded_channel();
+        omegga.spawn_task(QueueTask::new(omegga.clone(), config).run(rx));
+        Self { tx }
+    }
+
+    /// Queue a message with a priority.
+    pub fn send(&self, priority: Priority, message: ChatMessage) {
+        let _ = self.tx.send((priority, message));
+    }
+
+    /// Queue a broadcast.
+    pub fn broadcast(&self, line: impl Into<String>) {
+        self.send(Priority::Normal, ChatMessage::Broadcast(line.into()));
+    }
+
+    /// Queue a whisper to a user by their name.
+    pub fn whisper(&self, username: impl Into<String>, line: impl Into<String>) {
+        self.send(
+            Priority::Normal,
+            ChatMessage::Whisper {
+                target: username.into(),
+                line: line.into(),
+            },
+        );
+    }
+
+    /// Queue a middle print to a user by their name.
+    pub fn middle_print(&self, username: impl Into<String>, line: impl Into<String>) {
+        self.send(
+            Priority::Normal,
+            ChatMessage::MiddlePrint {
+                target: username.into(),
+                line: line.into(),
+            },
+        );
+    }
+}
+
+/// A sliding window of the times messages were sent.
+#[derive(Default)]
+struct Window(VecDeque<Instant>);
+
+impl Window {
+    /// The time at which another message can be sent under `limit`.
+    fn next_free(&mut self, limit: &RateLimit, now: Instant) -> Instant {
+        while matches!(self.0.front(), Some(&t) if now.duration_since(t) >= limit.per) {
+            self.0.pop_front();
+        }
+
+        if self.0.len() < limit.messages {
+            now
+        } else {
+            self.0[self.0.len() - limit.messages] + limit.per
+        }
+    }
+}
+
+struct QueueTask {
+    omegga: Omegga,
+    config: ChatQueueConfig,
+    /// Queued messages, by priority.
+    queues: [VecDeque<ChatMessage>; 3],
+    global: Window,
+    targets: HashMap<String, Window>,
+    /// The last message queued for each target, for de-duplication.
+    last: HashMap<Option<String>, (Instant, ChatMessage)>,
+}
+
+impl QueueTask {
+    fn new(omegga: Omegga, config: ChatQueueConfig) -> Self {
+        Self {
+            omegga,
+            config,
+            queues: Default::default(),
+            global: Window::default(),
+            targets: HashMap::new(),
+            last: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, priority: Priority, message: ChatMessage) {
+        if let Some(dedupe) = self.config.dedupe {
+            let now = Instant::now();
+            let key = message.target().map(String::from);
+            if let Some((t, last)) = self.last.get(&key) {
+                if *last == message && now.duration_since(*t) < dedupe {
+                    return;
+                }
+            }
+            self.last.insert(key, (now, message.clone()));
+        }
+
+        self.queues[priority as usize].push_back(message);
+    }
+
+    /// Send every message that can be sent now, returning the time at which
+    /// the next message can be sent, if any are left.
+    fn send_ready(&mut self) -> Option<Instant> {
+        loop {
+            let now = Instant::now();
+            let mut wake: Option<Instant> = None;
+
+            if let Some(limit) = self.config.global {
+                let free = self.global.next_free(&limit, now);
+                if free > now {
+                    return self.queues.iter().any(|q| !q.is_empty()).then_some(free);
+                }
+            }
+
+            // find the first message, by priority, whose target isn't rate limited
+            let mut found = None;
+            'outer: for p in (0..self.queues.len()).rev() {
+                for (i, message) in self.queues[p].iter().enumerate() {
+                    let free = match (message.target(), self.config.per_target) {
+                        (Some(target), Some(limit)) => self
+                            .targets
+                            .entry(target.to_string())
+                            .or_default()
+                            .next_free(&limit, now),
+                        _ => now,
+                    };
+
+                    if free <= now {
+                        found = Some((p, i));
+                        break 'outer;
+                    }
+                    wake = Some(wake.map_or(free, |w| w.min(free)));
+                }
+            }
+
+            let (p, i) = match found {
+                Some(found) => found,
+                None => return wake,
+            };
+
+            let message = self.queues[p].remove(i).unwrap();
+            if self.config.global.is_some() {
+                self.global.0.push_back(now);
+            }
+            if let (Some(target), Some(_)) = (message.target(), self.config.per_target) {
+                self.targets
+                    .entry(target.to_string())
+                    .or_default()
+                    .0
+                    .push_back(now);
+            }
+            message.send(&self.omegga);
+        }
+    }
+
+    async fn run(mut self, mut rx: mpsc::UnboundedReceiver<(Priority, ChatMessage)>) {
+        let mut open = true;
+        loop {
+            let wake = self.send_ready();
+            if !open && wake.is_none() {
+                break;
+            }
+
+            tokio::select! {
+                received = rx.recv(), if open => match received {
+                    Some((priority, message)) => self.push(priority, message),
+                    None => open = false,
+                },
+                _ = time::sleep_until(wake.unwrap_or_else(Instant::now)), if wake.is_some() => (),
+            }
+
+            // forget players that haven't been messaged recently
+            if self.targets.len() + self.last.len() > 128 {
+                let now = Instant::now();
+                if let Some(limit) = self.config.per_target {
+                    self.targets.retain(|_, w| {
+                        w.next_free(&limit, now);
+                        !w.0.is_empty()
+                    });
+                }
+                if let Some(dedupe) = self.config.dedupe {
+                    self.last
+                        .retain(|_, (t, _)| now.duration_since(*t) < dedupe);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+    use crate::rpc;
+
+    const SECOND: Duration = Duration::from_secs(1);
+
+    /// A queue task whose [`Omegga`] keeps what it writes instead of printing it.
+    fn task(config: ChatQueueConfig) -> QueueTask {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+        QueueTask::new(omegga, config)
+    }
+
+    /// The messages sent since this was last called, as `line` or `target: line`.
+    fn sent(omegga: &Omegga) -> Vec<String> {
+        omegga
+            .replay_output()
+            .into_iter()
+            .filter_map(|message| match message {
+                rpc::Message::Notification { params, .. } => match params? {
+                    Value::String(line) => Some(line),
+                    params => {
+                        Some(format!("{}: {}", params["target"], params["line"]).replace('"', ""))
+                    }
+                },
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn whisper(target: &str, line: &str) -> ChatMessage {
+        ChatMessage::Whisper {
+            target: target.into(),
+            line: line.into(),
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn sends_by_priority() {
+        let mut task = task(ChatQueueConfig {
+            global: Some(RateLimit::new(1, SECOND)),
+            per_target: None,
+            dedupe: None,
+        });
+        task.push(Priority::Low, ChatMessage::Broadcast("low".into()));
+        task.push(Priority::Normal, ChatMessage::Broadcast("normal 1".into()));
+        task.push(Priority::Normal, ChatMessage::Broadcast("normal 2".into()));
+        task.push(Priority::High, ChatMessage::Broadcast("high".into()));
+
+        let start = Instant::now();
+        assert_eq!(task.send_ready(), Some(start + SECOND));
+        assert_eq!(sent(&task.omegga), ["high"]);
+
+        let mut lines = vec![];
+        time::sleep_until(start + SECOND).await;
+        while let Some(wake) = task.send_ready() {
+            lines.extend(sent(&task.omegga));
+            time::sleep_until(wake).await;
+        }
+        lines.extend(sent(&task.omegga));
+        assert_eq!(lines, ["normal 1", "normal 2", "low"]);
+        assert_eq!(Instant::now(), start + 3 * SECOND);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drops_duplicates() {
+        let mut task = task(ChatQueueConfig {
+            global: None,
+            per_target: None,
+            dedupe: Some(5 * SECOND),
+        });
+        task.push(Priority::Normal, whisper("a", "hi"));
+        task.push(Priority::Normal, whisper("a", "hi"));
+        task.push(Priority::High, whisper("a", "hi"));
+        // the same line to another target, or another line, isn't a duplicate
+        task.push(Priority::Normal, whisper("b", "hi"));
+        task.push(Priority::Normal, whisper("a", "bye"));
+        task.push(Priority::Normal, whisper("a", "hi"));
+        assert_eq!(task.send_ready(), None);
+        assert_eq!(sent(&task.omegga), ["a: hi", "b: hi", "a: bye", "a: hi"]);
+
+        task.push(Priority::Normal, whisper("a", "hi"));
+        time::advance(5 * SECOND).await;
+        task.push(Priority::Normal, whisper("a", "hi"));
+        task.send_ready();
+        assert_eq!(sent(&task.omegga), ["a: hi"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn limits_each_target() {
+        let mut task = task(ChatQueueConfig {
+            global: None,
+            per_target: Some(RateLimit::new(1, SECOND)),
+            dedupe: None,
+        });
+        task.push(Priority::Normal, whisper("a", "1"));
+        task.push(Priority::Normal, whisper("a", "2"));
+        task.push(Priority::Normal, whisper("b", "3"));
+        task.push(
+            Priority::Normal,
+            ChatMessage::MiddlePrint {
+                target: "a".into(),
+                line: "4".into(),
+            },
+        );
+        task.push(Priority::Low, ChatMessage::Broadcast("5".into()));
+
+        // a rate limited target doesn't hold up other targets or broadcasts
+        let start = Instant::now();
+        assert_eq!(task.send_ready(), Some(start + SECOND));
+        assert_eq!(sent(&task.omegga), ["a: 1", "b: 3", "5"]);
+
+        time::advance(SECOND).await;
+        assert_eq!(task.send_ready(), Some(start + 2 * SECOND));
+        assert_eq!(sent(&task.omegga), ["a: 2"]);
+
+        time::advance(SECOND).await;
+        assert_eq!(task.send_ready(), None);
+        assert_eq!(sent(&task.omegga), ["a: 4"]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drains_after_the_queue_is_dropped() {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+        let queue = ChatQueue::new(
+            &omegga,
+            ChatQueueConfig {
+                global: Some(RateLimit::new(2, SECOND)),
+                ..ChatQueueConfig::default()
+            },
+        );
+        for i in 0..5 {
+            queue.broadcast(i.to_string());
+        }
+        drop(queue);
+
+        time::sleep(SECOND / 2).await;
+        assert_eq!(sent(&omegga), ["0", "1"]);
+        time::sleep(2 * SECOND).await;
+        assert_eq!(sent(&omegga), ["2", "3", "4"]);
+    }
+
+    #[test]
+    fn clamps_rate_limit() {
+        let limit = RateLimit::new(0, Duration::from_secs(1));
+        assert_eq!(limit.messages(), 1);
+
+        let now = Instant::now();
+        let mut window = Window::default();
+        assert_eq!(window.next_free(&limit, now), now);
+        window.0.push_back(now);
+        assert_eq!(window.next_free(&limit, now), now + Duration::from_secs(1));
+    }
+
+    #[test]
+    fn slides_window() {
+        let limit = RateLimit::new(2, Duration::from_secs(1));
+        let start = Instant::now();
+        let mut window = Window::default();
+        window.0.push_back(start);
+        assert_eq!(window.next_free(&limit, start), start);
+
+        window.0.push_back(start + Duration::from_millis(500));
+        let now = start + Duration::from_millis(600);
+        assert_eq!(
+            window.next_free(&limit, now),
+            start + Duration::from_secs(1)
+        );
+
+        // the first message leaves the window
+        let now = start + Duration::from_secs(1);
+        assert_eq!(window.next_free(&limit, now), now);
+        assert_eq!(window.0.len(), 1);
+    }
+}
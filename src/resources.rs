@@ -2,12 +2,14 @@ use std::collections::HashMap;
 
 use serde::{Deserialize, Serialize};
 
+use crate::{geometry::Vec3, orientation::Orientation};
+
 macro_rules! color_def {
     ($n:ident, $c:literal) => {
         fn $n(self) -> Self {
             self.color($c)
         }
-    }
+    };
 }
 
 pub trait Colorize: Sized {
@@ -47,12 +49,16 @@ impl Colorize for String {
     }
 
     fn size(self, size: i32) -> Self {
-        format!("<size=\"{}\">{}</>", self, size)
+        format!("<size=\"{}\">{}</>", size, self)
     }
 }
 
 /// A player.
+///
+/// Players can be deserialized from an object, or from the `[name, id, controller, state]`
+/// array Omegga sends in its bootstrap.
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "PlayerRepr")]
 pub struct Player {
     pub name: String,
     pub id: String,
@@ -61,11 +67,73 @@ pub struct Player {
     pub host: Option<bool>,
 }
 
-/// A player position, which composes a `Player` and their position (a `(f64, f64, f64)`).
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PlayerRepr {
+    Object {
+        name: String,
+        id: String,
+        controller: String,
+        state: String,
+        host: Option<bool>,
+    },
+    Raw(String, String, String, String),
+}
+
+impl From<PlayerRepr> for Player {
+    fn from(repr: PlayerRepr) -> Self {
+        match repr {
+            PlayerRepr::Object {
+                name,
+                id,
+                controller,
+                state,
+                host,
+            } => Player {
+                name,
+                id,
+                controller,
+                state,
+                host,
+            },
+            PlayerRepr::Raw(name, id, controller, state) => Player {
+                name,
+                id,
+                controller,
+                state,
+                host: None,
+            },
+        }
+    }
+}
+
+/// The server's host.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Host {
+    pub name: String,
+    pub id: String,
+}
+
+/// What's known about the server, kept up to date by
+/// [`Omegga::handle_message`](crate::Omegga::handle_message).
+#[derive(Debug, Clone, Default)]
+pub struct ServerInfo {
+    pub host: Option<Host>,
+    /// The current map.
+    pub map: Option<String>,
+    /// The game's changelist number.
+    pub version: Option<u32>,
+    /// The path to the server's directory.
+    pub path: Option<String>,
+    /// The path to the server's saves.
+    pub save_path: Option<String>,
+}
+
+/// A player position, which composes a `Player` and their position.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerPosition {
     pub player: Player,
-    pub pos: Option<(f64, f64, f64)>,
+    pub pos: Option<Vec3>,
 }
 
 /// Ghost brick data.
@@ -73,31 +141,151 @@ pub struct PlayerPosition {
 pub struct GhostBrick {
     #[serde(rename = "targetGrid")]
     pub target_grid: String,
-    pub location: (f64, f64, f64),
-    pub orientation: String,
+    pub location: Vec3,
+    pub orientation: Orientation,
+}
+
+/// A brick material.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum Material {
+    #[default]
+    Plastic,
+    Glass,
+    Glow,
+    Metallic,
+    Hologram,
+    Ghost,
+    GhostFail,
+    /// A material not known to this crate, by its asset name.
+    Other(String),
+}
+
+impl Material {
+    /// The materials in the order Omegga indexes them, as in `PlayerPaint::material_index`.
+    pub const DEFAULT_ORDER: [Material; 7] = [
+        Material::Ghost,
+        Material::GhostFail,
+        Material::Plastic,
+        Material::Glass,
+        Material::Glow,
+        Material::Metallic,
+        Material::Hologram,
+    ];
+
+    /// The material's asset name, like `BMC_Plastic`.
+    pub fn asset_name(&self) -> &str {
+        match self {
+            Material::Plastic => "BMC_Plastic",
+            Material::Glass => "BMC_Glass",
+            Material::Glow => "BMC_Glow",
+            Material::Metallic => "BMC_Metallic",
+            Material::Hologram => "BMC_Hologram",
+            Material::Ghost => "BMC_Ghost",
+            Material::GhostFail => "BMC_Ghost_Fail",
+            Material::Other(name) => name,
+        }
+    }
+
+    /// Get a material by its asset name, like `BMC_Plastic`.
+    pub fn from_asset_name(name: &str) -> Self {
+        Material::DEFAULT_ORDER
+            .into_iter()
+            .find(|m| m.asset_name() == name)
+            .unwrap_or_else(|| Material::Other(name.to_string()))
+    }
+}
+
+impl From<String> for Material {
+    fn from(value: String) -> Self {
+        Material::from_asset_name(&value)
+    }
+}
+
+impl From<Material> for String {
+    fn from(value: Material) -> Self {
+        value.asset_name().to_string()
+    }
+}
+
+/// Deserializes a number that may be sent as a string.
+fn number_or_string<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<u32, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(u32),
+        String(String),
+    }
+
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(n) => Ok(n),
+        NumberOrString::String(s) => s.trim().parse().map_err(serde::de::Error::custom),
+    }
+}
+
+/// Deserializes a version that may be sent as a number or a string, as a string.
+fn string_or_number<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum StringOrNumber {
+        String(String),
+        Number(serde_json::Number),
+    }
+
+    Ok(match StringOrNumber::deserialize(deserializer)? {
+        StringOrNumber::String(s) => s,
+        StringOrNumber::Number(n) => n.to_string(),
+    })
 }
 
 /// Player paint data.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PlayerPaint {
-    #[serde(rename = "materialIndex")]
-    pub material_index: String,
-    #[serde(rename = "materialAlpha")]
-    pub material_alpha: String,
-    #[serde(rename = "material")]
-    pub material: String,
+    #[serde(rename = "materialIndex", deserialize_with = "number_or_string")]
+    pub material_index: u32,
+    /// The material intensity, from 0 to 10.
+    #[serde(rename = "materialAlpha", deserialize_with = "number_or_string")]
+    pub material_alpha: u32,
+    #[serde(rename = "material", default)]
+    pub material: Material,
     #[serde(rename = "color")]
     pub color: (u8, u8, u8),
 }
 
+#[cfg(feature = "brs")]
+impl PlayerPaint {
+    /// Apply this paint to a brick, adding the material to a save's material list
+    /// (`save.header2.materials`) if needed.
+    ///
+    /// The list and the brick are borrowed separately, so a brick in the same save can
+    /// be painted with `paint.apply(&mut save.header2.materials, &mut save.bricks[i])`.
+    pub fn apply(&self, materials: &mut Vec<String>, brick: &mut brickadia::save::Brick) {
+        let name = self.material.asset_name();
+        brick.material_index = match materials.iter().position(|m| m == name) {
+            Some(i) => i as u32,
+            None => {
+                materials.push(name.to_string());
+                materials.len() as u32 - 1
+            }
+        };
+        brick.material_intensity = self.material_alpha;
+        brick.color = brickadia::save::BrickColor::Unique(brickadia::save::Color {
+            r: self.color.0,
+            g: self.color.1,
+            b: self.color.2,
+            a: 255,
+        });
+    }
+}
+
 /// Bounds data.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TemplateBounds {
     #[serde(rename = "minBound")]
-    pub min_bound: (f64, f64, f64),
+    pub min_bound: Vec3,
     #[serde(rename = "maxBound")]
-    pub max_bound: (f64, f64, f64),
-    pub center: (f64, f64, f64),
+    pub max_bound: Vec3,
+    pub center: Vec3,
 }
 
 /// A plugin.
@@ -139,3 +327,567 @@ pub struct CommandArg {
     #[serde(default)]
     pub required: bool,
 }
+
+/// A color with an alpha channel, as used in the role setup and minigame teams.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Color {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    #[serde(default = "Color::opaque")]
+    pub a: u8,
+}
+
+impl Color {
+    fn opaque() -> u8 {
+        255
+    }
+}
+
+/// The state of a permission on a role.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PermissionState {
+    #[default]
+    Unchanged,
+    Allowed,
+    Forbidden,
+}
+
+/// A permission on a role.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RolePermission {
+    pub name: String,
+    pub state: PermissionState,
+}
+
+/// A role from the role setup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Role {
+    pub name: String,
+    #[serde(default)]
+    pub permissions: Vec<RolePermission>,
+    #[serde(rename = "defaultPermissions", default)]
+    pub default_permissions: PermissionState,
+    #[serde(default)]
+    pub color: Color,
+    #[serde(rename = "bHasColor", default)]
+    pub has_color: bool,
+}
+
+impl Role {
+    /// Get the state of a permission on this role, falling back to the role's default.
+    pub fn permission(&self, name: &str) -> PermissionState {
+        self.permissions
+            .iter()
+            .find(|p| p.name == name)
+            .map(|p| p.state)
+            .unwrap_or(self.default_permissions)
+    }
+}
+
+/// The data of the role setup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleSetupData {
+    #[serde(default)]
+    pub roles: Vec<Role>,
+    #[serde(rename = "defaultRole")]
+    pub default_role: Option<Role>,
+    #[serde(rename = "ownerRoleColor", default)]
+    pub owner_role_color: Color,
+    #[serde(rename = "bOwnerRoleHasColor", default)]
+    pub owner_role_has_color: bool,
+    #[serde(rename = "defaultRoleColor", default)]
+    pub default_role_color: Color,
+    #[serde(rename = "bDefaultRoleHasColor", default)]
+    pub default_role_has_color: bool,
+    /// A map of player IDs to the names of their roles.
+    #[serde(rename = "userRoles", default)]
+    pub user_roles: HashMap<String, Vec<String>>,
+}
+
+/// The server's role setup.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RoleSetup {
+    #[serde(
+        rename = "formatVersion",
+        default,
+        deserialize_with = "string_or_number"
+    )]
+    pub format_version: String,
+    #[serde(
+        rename = "presetVersion",
+        default,
+        deserialize_with = "string_or_number"
+    )]
+    pub preset_version: String,
+    pub data: RoleSetupData,
+}
+
+impl RoleSetup {
+    /// Get a role by its name.
+    pub fn role(&self, name: &str) -> Option<&Role> {
+        self.data.roles.iter().find(|r| r.name == name)
+    }
+
+    /// Get the roles assigned to a player by their ID.
+    pub fn player_roles(&self, id: &str) -> Vec<&Role> {
+        self.data
+            .user_roles
+            .get(id)
+            .map(|names| names.iter().filter_map(|n| self.role(n)).collect())
+            .unwrap_or_default()
+    }
+}
+
+/// A ban list entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanEntry {
+    #[serde(rename = "bannerId")]
+    pub banner_id: String,
+    pub created: String,
+    pub expires: String,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// The data of the ban list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanListData {
+    /// A map of banned player IDs to their ban entries.
+    #[serde(rename = "banList", default)]
+    pub ban_list: HashMap<String, BanEntry>,
+}
+
+/// The server's ban list.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BanList {
+    #[serde(
+        rename = "formatVersion",
+        default,
+        deserialize_with = "string_or_number"
+    )]
+    pub format_version: String,
+    #[serde(
+        rename = "presetVersion",
+        default,
+        deserialize_with = "string_or_number"
+    )]
+    pub preset_version: String,
+    pub data: BanListData,
+}
+
+impl BanList {
+    /// Get the ban entry for a player by their ID.
+    pub fn get(&self, id: &str) -> Option<&BanEntry> {
+        self.data.ban_list.get(id)
+    }
+
+    /// Whether a player is on the ban list, by their ID.
+    ///
+    /// **Note:** This does not check whether the ban has expired.
+    pub fn contains(&self, id: &str) -> bool {
+        self.data.ban_list.contains_key(id)
+    }
+}
+
+/// A player's permissions, a map of permission names to whether they are allowed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Permissions(pub HashMap<String, bool>);
+
+impl Permissions {
+    /// Whether a permission (like `Bricks.ClearAll`) is allowed.
+    pub fn has(&self, permission: &str) -> bool {
+        self.0.get(permission).copied().unwrap_or(false)
+    }
+
+    /// Iterate over the names of allowed permissions.
+    pub fn allowed(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().filter(|(_, v)| **v).map(|(k, _)| k.as_str())
+    }
+}
+
+/// A team in a minigame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MinigameTeam {
+    pub name: String,
+    /// The team's ID in the minigame's ruleset.
+    pub team: String,
+    #[serde(default)]
+    pub color: Color,
+    #[serde(default)]
+    pub members: Vec<Player>,
+}
+
+/// A minigame running on the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Minigame {
+    /// The minigame's index, used to reset or save it.
+    pub index: u32,
+    pub name: String,
+    pub ruleset: String,
+    #[serde(default)]
+    pub members: Vec<Player>,
+    #[serde(default)]
+    pub teams: Vec<MinigameTeam>,
+}
+
+impl Minigame {
+    /// Get the team a player is on, by their ID.
+    pub fn team_of(&self, id: &str) -> Option<&MinigameTeam> {
+        self.teams
+            .iter()
+            .find(|t| t.members.iter().any(|p| p.id == id))
+    }
+
+    /// Whether a player, by their ID, is in this minigame.
+    pub fn has_member(&self, id: &str) -> bool {
+        self.members.iter().any(|p| p.id == id)
+    }
+}
+
+/// The data of an environment preset, a map of groups (like `Sky`, `Ground` and `Water`)
+/// to their settings.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EnvironmentData {
+    #[serde(default)]
+    pub groups: HashMap<String, serde_json::Map<String, serde_json::Value>>,
+}
+
+/// An environment preset, holding settings like the time of day, weather and water.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Environment {
+    #[serde(
+        rename = "formatVersion",
+        default,
+        deserialize_with = "string_or_number"
+    )]
+    pub format_version: String,
+    #[serde(
+        rename = "presetVersion",
+        default,
+        deserialize_with = "string_or_number"
+    )]
+    pub preset_version: String,
+    #[serde(rename = "type", default)]
+    pub kind: String,
+    #[serde(default)]
+    pub data: EnvironmentData,
+}
+
+impl Environment {
+    /// Get a setting, by its group and name, like `("Sky", "timeOfDay")`.
+    pub fn get(&self, group: &str, name: &str) -> Option<&serde_json::Value> {
+        self.data.groups.get(group)?.get(name)
+    }
+
+    /// Set a setting, by its group and name.
+    pub fn set(
+        &mut self,
+        group: impl Into<String>,
+        name: impl Into<String>,
+        value: impl Into<serde_json::Value>,
+    ) {
+        self.data
+            .groups
+            .entry(group.into())
+            .or_default()
+            .insert(name.into(), value.into());
+    }
+
+    fn get_f64(&self, group: &str, name: &str) -> Option<f64> {
+        self.get(group, name).and_then(serde_json::Value::as_f64)
+    }
+
+    /// The time of day, in hours from 0 to 24.
+    pub fn time_of_day(&self) -> Option<f64> {
+        self.get_f64("Sky", "timeOfDay")
+    }
+
+    /// Set the time of day, in hours from 0 to 24.
+    pub fn set_time_of_day(&mut self, hours: f64) {
+        self.set("Sky", "timeOfDay", hours);
+    }
+
+    /// How fast time passes, where 0 stops the day/night cycle.
+    pub fn time_change_speed(&self) -> Option<f64> {
+        self.get_f64("Sky", "timeChangeSpeed")
+    }
+
+    /// Set how fast time passes, where 0 stops the day/night cycle.
+    pub fn set_time_change_speed(&mut self, speed: f64) {
+        self.set("Sky", "timeChangeSpeed", speed);
+    }
+
+    /// The weather intensity, from 0 to 1.
+    pub fn weather_intensity(&self) -> Option<f64> {
+        self.get_f64("Sky", "weatherIntensity")
+    }
+
+    /// Set the weather intensity, from 0 to 1.
+    pub fn set_weather_intensity(&mut self, intensity: f64) {
+        self.set("Sky", "weatherIntensity", intensity);
+    }
+
+    /// Whether weather is rain (0) or snow (1), or a mix of both.
+    pub fn rain_snow(&self) -> Option<f64> {
+        self.get_f64("Sky", "rainSnow")
+    }
+
+    /// Set whether weather is rain (0) or snow (1), or a mix of both.
+    pub fn set_rain_snow(&mut self, rain_snow: f64) {
+        self.set("Sky", "rainSnow", rain_snow);
+    }
+
+    /// The cloud coverage, from 0 to 1.
+    pub fn cloud_coverage(&self) -> Option<f64> {
+        self.get_f64("Sky", "cloudCoverage")
+    }
+
+    /// Set the cloud coverage, from 0 to 1.
+    pub fn set_cloud_coverage(&mut self, coverage: f64) {
+        self.set("Sky", "cloudCoverage", coverage);
+    }
+
+    /// The height of the water.
+    pub fn water_height(&self) -> Option<f64> {
+        self.get_f64("Water", "waterHeight")
+    }
+
+    /// Set the height of the water.
+    pub fn set_water_height(&mut self, height: f64) {
+        self.set("Water", "waterHeight", height);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROLE_SETUP: &str = r#"{
+        "formatVersion": "1",
+        "presetVersion": 1,
+        "data": {
+            "roles": [
+                {
+                    "name": "Admin",
+                    "permissions": [
+                        { "name": "Bricks.ClearAll", "state": "Allowed" },
+                        { "name": "Players.Ban", "state": "Forbidden" }
+                    ],
+                    "defaultPermissions": "Unchanged",
+                    "color": { "r": 255, "g": 0, "b": 0, "a": 255 },
+                    "bHasColor": true
+                },
+                { "name": "Builder", "defaultPermissions": "Allowed" }
+            ],
+            "defaultRole": {
+                "name": "Default",
+                "permissions": [],
+                "color": { "r": 255, "g": 255, "b": 255 },
+                "bHasColor": false
+            },
+            "ownerRoleColor": { "r": 255, "g": 170, "b": 0, "a": 255 },
+            "bOwnerRoleHasColor": true,
+            "userRoles": {
+                "00000000-0000-0000-0000-000000000001": ["Admin", "Missing"]
+            }
+        }
+    }"#;
+
+    const BAN_LIST: &str = r#"{
+        "formatVersion": 1,
+        "presetVersion": "1",
+        "data": {
+            "banList": {
+                "00000000-0000-0000-0000-000000000002": {
+                    "bannerId": "00000000-0000-0000-0000-000000000001",
+                    "created": "2021.09.24-21.13.45",
+                    "expires": "2021.09.25-21.13.45",
+                    "reason": "griefing"
+                },
+                "00000000-0000-0000-0000-000000000003": {
+                    "bannerId": "00000000-0000-0000-0000-000000000001",
+                    "created": "2021.09.24-21.13.45",
+                    "expires": "2021.09.24-22.13.45"
+                }
+            }
+        }
+    }"#;
+
+    #[test]
+    fn parses_role_setup() {
+        let setup = serde_json::from_str::<RoleSetup>(ROLE_SETUP).unwrap();
+        assert_eq!(setup.format_version, "1");
+        assert_eq!(setup.preset_version, "1");
+
+        let admin = setup.role("Admin").unwrap();
+        assert_eq!(
+            admin.permission("Bricks.ClearAll"),
+            PermissionState::Allowed
+        );
+        assert_eq!(admin.permission("Players.Ban"), PermissionState::Forbidden);
+        assert_eq!(admin.permission("Players.Kick"), PermissionState::Unchanged);
+        assert_eq!(
+            admin.color,
+            Color {
+                r: 255,
+                g: 0,
+                b: 0,
+                a: 255
+            }
+        );
+        assert!(admin.has_color);
+
+        let builder = setup.role("Builder").unwrap();
+        assert_eq!(
+            builder.permission("Bricks.ClearAll"),
+            PermissionState::Allowed
+        );
+
+        let default = setup.data.default_role.as_ref().unwrap();
+        assert_eq!(default.color.a, 255);
+        assert!(setup.data.owner_role_has_color);
+        assert!(!setup.data.default_role_has_color);
+
+        let roles = setup.player_roles("00000000-0000-0000-0000-000000000001");
+        assert_eq!(roles.len(), 1);
+        assert_eq!(roles[0].name, "Admin");
+        assert!(setup.player_roles("unknown").is_empty());
+    }
+
+    #[test]
+    fn parses_ban_list() {
+        let bans = serde_json::from_str::<BanList>(BAN_LIST).unwrap();
+        assert_eq!(bans.format_version, "1");
+        assert_eq!(bans.preset_version, "1");
+
+        let ban = bans.get("00000000-0000-0000-0000-000000000002").unwrap();
+        assert_eq!(ban.banner_id, "00000000-0000-0000-0000-000000000001");
+        assert_eq!(ban.expires, "2021.09.25-21.13.45");
+        assert_eq!(ban.reason, "griefing");
+        assert_eq!(
+            bans.get("00000000-0000-0000-0000-000000000003")
+                .unwrap()
+                .reason,
+            ""
+        );
+        assert!(!bans.contains("00000000-0000-0000-0000-000000000001"));
+
+        let empty = serde_json::from_str::<BanList>(r#"{"data":{}}"#).unwrap();
+        assert!(empty.data.ban_list.is_empty());
+        assert_eq!(empty.format_version, "");
+    }
+
+    #[test]
+    fn parses_permissions() {
+        let permissions = serde_json::from_str::<Permissions>(
+            r#"{ "Bricks.ClearAll": true, "Players.Kick": false, "Players.Ban": true }"#,
+        )
+        .unwrap();
+        assert!(permissions.has("Bricks.ClearAll"));
+        assert!(!permissions.has("Players.Kick"));
+        assert!(!permissions.has("Players.TPInMinigame"));
+
+        let mut allowed = permissions.allowed().collect::<Vec<_>>();
+        allowed.sort();
+        assert_eq!(allowed, ["Bricks.ClearAll", "Players.Ban"]);
+    }
+
+    #[test]
+    fn parses_numbers_sent_as_strings() {
+        let paint = |json: &str| serde_json::from_str::<PlayerPaint>(json);
+
+        let numbers = paint(
+            r#"{"materialIndex":2,"materialAlpha":5,"material":"BMC_Plastic","color":[255,0,0]}"#,
+        )
+        .unwrap();
+        let strings = paint(
+            r#"{"materialIndex":"2","materialAlpha":" 5 ","material":"BMC_Plastic","color":[255,0,0]}"#,
+        )
+        .unwrap();
+        for paint in [numbers, strings] {
+            assert_eq!(paint.material_index, 2);
+            assert_eq!(paint.material_alpha, 5);
+            assert_eq!(paint.material, Material::Plastic);
+            assert_eq!(paint.color, (255, 0, 0));
+        }
+
+        assert!(paint(r#"{"materialIndex":"two","materialAlpha":5,"color":[0,0,0]}"#).is_err());
+        assert!(paint(r#"{"materialIndex":-1,"materialAlpha":5,"color":[0,0,0]}"#).is_err());
+        assert_eq!(
+            paint(r#"{"materialIndex":0,"materialAlpha":0,"color":[0,0,0]}"#)
+                .unwrap()
+                .material,
+            Material::Plastic
+        );
+    }
+
+    #[test]
+    fn converts_materials_to_and_from_strings() {
+        for material in Material::DEFAULT_ORDER {
+            let name = String::from(material.clone());
+            assert_eq!(Material::from(name), material);
+        }
+        assert_eq!(String::from(Material::GhostFail), "BMC_Ghost_Fail");
+        assert_eq!(Material::from("BMC_Glow".to_string()), Material::Glow);
+
+        // unknown assets are kept by name
+        let other = Material::from("BMC_Custom".to_string());
+        assert_eq!(other, Material::Other("BMC_Custom".to_string()));
+        assert_eq!(String::from(other), "BMC_Custom");
+        assert_eq!(
+            Material::from("bmc_plastic".to_string()),
+            Material::Other("bmc_plastic".to_string())
+        );
+    }
+
+    #[test]
+    fn serializes_materials_as_strings() {
+        assert_eq!(
+            serde_json::to_string(&Material::Metallic).unwrap(),
+            r#""BMC_Metallic""#
+        );
+        assert_eq!(
+            serde_json::from_str::<Material>(r#""BMC_Hologram""#).unwrap(),
+            Material::Hologram
+        );
+        assert_eq!(
+            serde_json::from_str::<Material>(r#""BMC_Custom""#).unwrap(),
+            Material::Other("BMC_Custom".to_string())
+        );
+    }
+
+    #[cfg(feature = "brs")]
+    #[test]
+    fn paints_bricks_in_a_save() {
+        use brickadia::save::{Brick, BrickColor, Color, SaveData};
+
+        let mut save = SaveData::default();
+        save.header2.materials = vec!["BMC_Plastic".to_string()];
+        save.bricks = vec![Brick::default(), Brick::default()];
+
+        let paint = |material, color| PlayerPaint {
+            material_index: 0,
+            material_alpha: 5,
+            material,
+            color,
+        };
+        paint(Material::Glow, (1, 2, 3)).apply(&mut save.header2.materials, &mut save.bricks[1]);
+        paint(Material::Plastic, (4, 5, 6)).apply(&mut save.header2.materials, &mut save.bricks[0]);
+
+        assert_eq!(save.header2.materials, ["BMC_Plastic", "BMC_Glow"]);
+        assert_eq!(save.bricks[1].material_index, 1);
+        assert_eq!(save.bricks[1].material_intensity, 5);
+        assert!(matches!(
+            save.bricks[1].color,
+            BrickColor::Unique(Color {
+                r: 1,
+                g: 2,
+                b: 3,
+                a: 255
+            })
+        ));
+        assert_eq!(save.bricks[0].material_index, 0);
+    }
+}
@@ -0,0 +1,497 @@
+use std::{
+    future::Future,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use dashmap::DashMap;
+use thiserror::Error;
+use tokio::{
+    runtime::Handle,
+    sync::oneshot,
+    time::{self, MissedTickBehavior},
+};
+
+struct Job {
+    /// Dropping this cancels the job.
+    _cancel: oneshot::Sender<()>,
+    player: Option<String>,
+}
+
+/// Schedules delayed, repeating and cron jobs.
+///
+/// Every [`Omegga`](crate::Omegga) has a scheduler, available from
+/// [`Omegga::scheduler`](crate::Omegga::scheduler). Its jobs are cancelled when
+/// the plugin is told to stop, and a player's jobs are cancelled when they leave.
+///
+/// Jobs run on the runtime the scheduler was created in, or the current runtime if it
+/// was created outside of one.
+#[derive(Clone, Default)]
+pub struct Scheduler {
+    jobs: Arc<DashMap<u64, Job>>,
+    next_id: Arc<AtomicU64>,
+    runtime: Option<Handle>,
+}
+
+/// A handle to a scheduled job, used to cancel it.
+///
+/// Dropping the handle does **not** cancel the job.
+#[derive(Clone)]
+pub struct JobHandle {
+    id: u64,
+    jobs: Arc<DashMap<u64, Job>>,
+}
+
+impl JobHandle {
+    /// The job's ID.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Cancel the job. A job that is running is stopped at its next `.await`.
+    pub fn cancel(&self) {
+        self.jobs.remove(&self.id);
+    }
+
+    /// Whether the job is still scheduled or running.
+    pub fn is_active(&self) -> bool {
+        self.jobs.contains_key(&self.id)
+    }
+}
+
+impl Scheduler {
+    /// Create a new scheduler with no jobs.
+    pub fn new() -> Self {
+        Self {
+            runtime: Handle::try_current().ok(),
+            ..Self::default()
+        }
+    }
+
+    fn spawn<Fut>(&self, player: Option<String>, job: Fut) -> JobHandle
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        let (cancel, cancelled) = oneshot::channel::<()>();
+        self.jobs.insert(
+            id,
+            Job {
+                _cancel: cancel,
+                player,
+            },
+        );
+
+        let jobs = Arc::clone(&self.jobs);
+        let task = async move {
+            tokio::select! {
+                _ = cancelled => (),
+                _ = job => (),
+            }
+            jobs.remove(&id);
+        };
+        match &self.runtime {
+            Some(runtime) => runtime.spawn(task),
+            None => tokio::spawn(task),
+        };
+
+        JobHandle {
+            id,
+            jobs: Arc::clone(&self.jobs),
+        }
+    }
+
+    async fn delayed<F, Fut>(delay: Duration, f: F)
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        time::sleep(delay).await;
+        f().await;
+    }
+
+    async fn repeating<F, Fut>(interval: Duration, f: F)
+    where
+        F: Fn() -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let mut interval = time::interval_at(time::Instant::now() + interval, interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            f().await;
+        }
+    }
+
+    /// Run a job now, until it finishes or is cancelled.
+    pub fn run<Fut>(&self, job: Fut) -> JobHandle
+    where
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn(None, job)
+    }
+
+    /// Run a job once, after a delay.
+    pub fn after<F, Fut>(&self, delay: Duration, f: F) -> JobHandle
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn(None, Self::delayed(delay, f))
+    }
+
+    /// Run a job repeatedly, first after `interval`, then every `interval`.
+    pub fn every<F, Fut>(&self, interval: Duration, f: F) -> JobHandle
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn(None, Self::repeating(interval, f))
+    }
+
+    /// Run a job whenever a cron schedule matches.
+    pub fn cron<F, Fut>(&self, schedule: Cron, f: F) -> JobHandle
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn(None, async move {
+            // the sleep is monotonic, so it can end a little before the wall clock reaches
+            // the slot; searching from the last fired slot keeps it from firing twice
+            let mut last_fired = 0;
+            loop {
+                let now = SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default();
+                let next = match schedule.next_after(now.as_secs().max(last_fired)) {
+                    Some(next) => next,
+                    None => return,
+                };
+                time::sleep(Duration::from_secs(next).saturating_sub(now)).await;
+                last_fired = next;
+                f().await;
+            }
+        })
+    }
+
+    /// Run a job once for a player, after a delay. The job is cancelled if the player leaves.
+    pub fn after_for<F, Fut>(&self, player: impl Into<String>, delay: Duration, f: F) -> JobHandle
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn(Some(player.into()), Self::delayed(delay, f))
+    }
+
+    /// Run a job repeatedly for a player, first after `interval`, then every `interval`.
+    /// The job is cancelled if the player leaves.
+    pub fn every_for<F, Fut>(
+        &self,
+        player: impl Into<String>,
+        interval: Duration,
+        f: F,
+    ) -> JobHandle
+    where
+        F: Fn() -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.spawn(Some(player.into()), Self::repeating(interval, f))
+    }
+
+    /// Cancel every job for a player, by the name or ID the job was scheduled with.
+    pub fn cancel_player(&self, player: &str) {
+        self.jobs
+            .retain(|_, job| job.player.as_deref() != Some(player));
+    }
+
+    /// Cancel every job.
+    pub fn cancel_all(&self) {
+        self.jobs.clear();
+    }
+
+    /// The number of scheduled or running jobs.
+    pub fn len(&self) -> usize {
+        self.jobs.len()
+    }
+
+    /// Whether there are no scheduled or running jobs.
+    pub fn is_empty(&self) -> bool {
+        self.jobs.is_empty()
+    }
+}
+
+/// An error parsing a [`Cron`] schedule.
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+#[error("invalid cron schedule: {0}")]
+pub struct ParseCronError(String);
+
+/// A cron schedule, like `*/5 * * * *`, evaluated in UTC.
+///
+/// The five fields are minute (0-59), hour (0-23), day of month (1-31), month (1-12)
+/// and day of week (0-6, or 7, where 0 and 7 are Sunday). Each field may be `*`, a
+/// number, a range (`1-5`), a step (`*/15`, `0-30/10`), or a list of these (`1,15,30`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Whether the day of month and day of week fields are `*`, which changes how
+    /// they combine.
+    days_any: bool,
+    weekdays_any: bool,
+}
+
+fn parse_field(field: &str, min: u32, max: u32) -> Option<u64> {
+    let mut bits = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|&s| s > 0)?),
+            None => (part, 1),
+        };
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((a, b)) => (a.parse().ok()?, b.parse().ok()?),
+                None => {
+                    let n = range.parse().ok()?;
+                    (n, if step > 1 { max } else { n })
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return None;
+        }
+        for n in (start..=end).step_by(step as usize) {
+            bits |= 1 << n;
+        }
+    }
+    Some(bits)
+}
+
+/// Convert days since the Unix epoch to a (year, month, day) date.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+impl Cron {
+    fn day_matches(&self, days: i64) -> bool {
+        let (_, month, day) = civil_from_days(days);
+        let weekday = (days + 4).rem_euclid(7) as u32;
+        if self.months & (1 << month) == 0 {
+            return false;
+        }
+
+        let day_ok = self.days & (1 << day) != 0;
+        let weekday_ok = self.weekdays & (1 << weekday) != 0;
+        match (self.days_any, self.weekdays_any) {
+            (true, true) => true,
+            (true, false) => weekday_ok,
+            (false, true) => day_ok,
+            (false, false) => day_ok || weekday_ok,
+        }
+    }
+
+    /// The first time the schedule matches after `time`, both in seconds since the Unix epoch.
+    pub fn next_after(&self, time: u64) -> Option<u64> {
+        // start at the next whole minute
+        let mut minute = time / 60 + 1;
+        let limit = minute + 60 * 24 * 366 * 8;
+
+        while minute < limit {
+            let days = (minute / (60 * 24)) as i64;
+            if !self.day_matches(days) {
+                minute = (minute / (60 * 24) + 1) * 60 * 24;
+                continue;
+            }
+
+            let hour = minute / 60 % 24;
+            if self.hours & (1 << hour) == 0 {
+                minute = (minute / 60 + 1) * 60;
+                continue;
+            }
+
+            if self.minutes & (1 << (minute % 60)) == 0 {
+                minute += 1;
+                continue;
+            }
+
+            return Some(minute * 60);
+        }
+
+        None
+    }
+}
+
+impl FromStr for Cron {
+    type Err = ParseCronError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseCronError(s.to_string());
+        let fields = s.split_whitespace().collect::<Vec<_>>();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(err());
+        };
+
+        let mut weekday_bits = parse_field(weekdays, 0, 7).ok_or_else(err)?;
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits |= 1;
+        }
+
+        Ok(Cron {
+            minutes: parse_field(minutes, 0, 59).ok_or_else(err)?,
+            hours: parse_field(hours, 0, 23).ok_or_else(err)?,
+            days: parse_field(days, 1, 31).ok_or_else(err)?,
+            months: parse_field(months, 1, 12).ok_or_else(err)?,
+            weekdays: weekday_bits,
+            days_any: days == "*",
+            weekdays_any: weekdays == "*",
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seconds since the Unix epoch for a UTC date and time.
+    fn at(days: i64, hour: u64, minute: u64) -> u64 {
+        days as u64 * 86400 + hour * 3600 + minute * 60
+    }
+
+    #[test]
+    fn parses_fields() {
+        assert_eq!(parse_field("*", 0, 3), Some(0b1111));
+        assert_eq!(parse_field("2", 0, 59), Some(1 << 2));
+        assert_eq!(parse_field("1-3", 0, 59), Some(0b1110));
+        assert_eq!(
+            parse_field("*/15", 0, 59),
+            Some(1 | 1 << 15 | 1 << 30 | 1 << 45)
+        );
+        assert_eq!(
+            parse_field("0-30/10", 0, 59),
+            Some(1 | 1 << 10 | 1 << 20 | 1 << 30)
+        );
+        assert_eq!(parse_field("50/5", 0, 59), Some(1 << 50 | 1 << 55));
+        assert_eq!(
+            parse_field("1,15,30", 1, 31),
+            Some(1 << 1 | 1 << 15 | 1 << 30)
+        );
+        assert_eq!(parse_field("1-2,5", 0, 59), Some(0b100110));
+    }
+
+    #[test]
+    fn rejects_invalid_fields() {
+        for field in ["", "60", "5-1", "*/0", "a", "1-", "-1", "1,,2", "*/x"] {
+            assert_eq!(parse_field(field, 0, 59), None, "{field:?}");
+        }
+        assert_eq!(parse_field("0", 1, 31), None);
+    }
+
+    #[test]
+    fn converts_days_to_dates() {
+        assert_eq!(civil_from_days(0), (1970, 1, 1));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        assert_eq!(civil_from_days(59), (1970, 3, 1));
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(11017), (2000, 3, 1));
+        assert_eq!(civil_from_days(19722), (2023, 12, 31));
+        assert_eq!(civil_from_days(19723), (2024, 1, 1));
+    }
+
+    #[test]
+    fn finds_next_minute() {
+        let cron = "* * * * *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(0), Some(60));
+        assert_eq!(cron.next_after(59), Some(60));
+        assert_eq!(cron.next_after(60), Some(120));
+    }
+
+    #[test]
+    fn finds_steps_and_lists() {
+        let cron = "*/15 * * * *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(at(0, 0, 0)), Some(at(0, 0, 15)));
+        assert_eq!(cron.next_after(at(0, 0, 50)), Some(at(0, 1, 0)));
+
+        let cron = "0 9,17 * * *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(at(0, 9, 0)), Some(at(0, 17, 0)));
+        assert_eq!(cron.next_after(at(0, 17, 0)), Some(at(1, 9, 0)));
+    }
+
+    #[test]
+    fn crosses_day_month_and_year_boundaries() {
+        let cron = "30 2 * * *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(at(0, 23, 59)), Some(at(1, 2, 30)));
+
+        // 1970-01-31 -> 1970-02-01
+        let cron = "0 0 1 * *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(at(30, 12, 0)), Some(at(31, 0, 0)));
+
+        // 2023-12-31 -> 2024-01-01
+        let cron = "0 0 1 1 *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(at(19722, 0, 0)), Some(at(19723, 0, 0)));
+
+        // the next 29th of February after 2000-03-01 is in 2004
+        let cron = "0 0 29 2 *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(at(11017, 0, 0)), Some(at(12477, 0, 0)));
+        assert_eq!(civil_from_days(12477), (2004, 2, 29));
+    }
+
+    #[test]
+    fn matches_days_of_week() {
+        // 1970-01-01 was a Thursday, so the first Sunday was the 4th
+        let sunday = "0 0 * * 0".parse::<Cron>().unwrap();
+        assert_eq!(sunday.next_after(at(0, 0, 0)), Some(at(3, 0, 0)));
+        let seven = "0 0 * * 7".parse::<Cron>().unwrap();
+        assert_eq!(seven.next_after(at(0, 0, 0)), Some(at(3, 0, 0)));
+
+        let weekdays = "0 0 * * 1-5".parse::<Cron>().unwrap();
+        assert_eq!(weekdays.next_after(at(1, 0, 0)), Some(at(4, 0, 0)));
+
+        // a day of month and a day of week match either
+        let either = "0 0 10 * 0".parse::<Cron>().unwrap();
+        assert_eq!(either.next_after(at(0, 0, 0)), Some(at(3, 0, 0)));
+        assert_eq!(either.next_after(at(8, 0, 0)), Some(at(9, 0, 0)));
+        assert_eq!(either.next_after(at(9, 0, 0)), Some(at(10, 0, 0)));
+    }
+
+    #[test]
+    fn never_matches_impossible_dates() {
+        let cron = "0 0 31 2 *".parse::<Cron>().unwrap();
+        assert_eq!(cron.next_after(0), None);
+    }
+
+    #[test]
+    fn rejects_invalid_schedules() {
+        for schedule in [
+            "",
+            "* * * *",
+            "* * * * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "* * 0 * *",
+            "* * * 13 *",
+            "* * * * 8",
+        ] {
+            assert_eq!(
+                schedule.parse::<Cron>(),
+                Err(ParseCronError(schedule.to_string())),
+                "{schedule:?}"
+            );
+        }
+    }
+}
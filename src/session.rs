@@ -0,0 +1,359 @@
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::Path,
+    sync::Mutex,
+    time::Instant,
+};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::rpc;
+
+/// Whether a recorded message was received from or sent to Omegga.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// A recorded message.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    /// When the message was received or sent, in milliseconds since recording started.
+    pub time: u64,
+    pub direction: Direction,
+    pub message: rpc::Message,
+}
+
+/// A recorded session, loaded from a file written by [`Omegga::record`](crate::Omegga::record).
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    pub entries: Vec<Entry>,
+}
+
+impl Recording {
+    /// Load a recording from a JSONL file, with one [`Entry`] per line.
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let reader = BufReader::new(File::open(path)?);
+        let mut entries = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            entries.push(
+                serde_json::from_str(&line)
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?,
+            );
+        }
+        Ok(Self { entries })
+    }
+
+    /// The messages that were received from Omegga, in order.
+    pub fn incoming(&self) -> impl Iterator<Item = &Entry> {
+        self.entries.iter().filter(|e| e.direction == Direction::In)
+    }
+
+    /// The messages that were sent to Omegga, in order.
+    pub fn outgoing(&self) -> impl Iterator<Item = &Entry> {
+        self.entries
+            .iter()
+            .filter(|e| e.direction == Direction::Out)
+    }
+
+    /// Compare the messages a plugin sent during a replay with the ones it sent when
+    /// this was recorded.
+    pub fn diff_output(&self, actual: &[rpc::Message]) -> Vec<Difference> {
+        let expected = self
+            .outgoing()
+            .map(|e| e.message.clone())
+            .collect::<Vec<_>>();
+        diff(&expected, actual)
+    }
+
+    /// Assert that a plugin sent the same messages during a replay as it did when this
+    /// was recorded.
+    ///
+    /// # Panics
+    ///
+    /// This panics, listing every difference, if the messages differ.
+    pub fn assert_output(&self, actual: &[rpc::Message]) {
+        let differences = self.diff_output(actual);
+        if !differences.is_empty() {
+            let list = differences
+                .iter()
+                .map(|d| d.to_string())
+                .collect::<Vec<_>>()
+                .join("\n");
+            panic!("replayed output differs from the recording:\n{}", list);
+        }
+    }
+}
+
+/// A difference between an expected and actual message, from [`diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// The index of the message in both lists.
+    pub index: usize,
+    /// The expected message, or `None` if there were more actual messages.
+    pub expected: Option<Value>,
+    /// The actual message, or `None` if there were fewer actual messages.
+    pub actual: Option<Value>,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let show = |v: &Option<Value>| match v {
+            Some(v) => v.to_string(),
+            None => "nothing".to_string(),
+        };
+        write!(
+            f,
+            "#{}: expected {}, got {}",
+            self.index,
+            show(&self.expected),
+            show(&self.actual)
+        )
+    }
+}
+
+/// Compare two lists of messages in order, returning every position they differ at.
+pub fn diff(expected: &[rpc::Message], actual: &[rpc::Message]) -> Vec<Difference> {
+    let to_value = |m: &rpc::Message| serde_json::to_value(m).unwrap_or(Value::Null);
+    (0..expected.len().max(actual.len()))
+        .filter_map(|index| {
+            let expected = expected.get(index).map(to_value);
+            let actual = actual.get(index).map(to_value);
+            (expected != actual).then_some(Difference {
+                index,
+                expected,
+                actual,
+            })
+        })
+        .collect()
+}
+
+struct Recorder {
+    start: Instant,
+    file: BufWriter<File>,
+}
+
+/// Records messages and captures replayed output for an [`Omegga`](crate::Omegga).
+#[derive(Default)]
+pub(crate) struct Session {
+    recorder: Mutex<Option<Recorder>>,
+    /// The messages written during a replay, which aren't sent to stdout.
+    replay_output: Mutex<Option<Vec<rpc::Message>>>,
+}
+
+impl Session {
+    pub(crate) fn start_recording(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        *self.recorder.lock().unwrap() = Some(Recorder {
+            start: Instant::now(),
+            file,
+        });
+        Ok(())
+    }
+
+    pub(crate) fn stop_recording(&self) {
+        if let Some(mut recorder) = self.recorder.lock().unwrap().take() {
+            let _ = recorder.file.flush();
+        }
+    }
+
+    pub(crate) fn record(&self, direction: Direction, message: &rpc::Message) {
+        Self::write_entry(self.recorder.lock().unwrap().as_mut(), direction, message);
+    }
+
+    /// Record a message, unless the recorder is in use or poisoned, for the panic hook,
+    /// which may run while the panicking thread holds the recorder.
+    pub(crate) fn try_record(&self, direction: Direction, message: &rpc::Message) {
+        if let Ok(mut recorder) = self.recorder.try_lock() {
+            Self::write_entry(recorder.as_mut(), direction, message);
+        }
+    }
+
+    fn write_entry(recorder: Option<&mut Recorder>, direction: Direction, message: &rpc::Message) {
+        let recorder = match recorder {
+            Some(recorder) => recorder,
+            None => return,
+        };
+
+        let entry = Entry {
+            time: recorder.start.elapsed().as_millis() as u64,
+            direction,
+            message: message.clone(),
+        };
+        if let Ok(line) = serde_json::to_string(&entry) {
+            // flush every entry, so the recording survives the plugin crashing
+            let _ = writeln!(recorder.file, "{}", line);
+            let _ = recorder.file.flush();
+        }
+    }
+
+    /// Capture a written message if replaying, returning whether it was captured.
+    pub(crate) fn capture(&self, message: &rpc::Message) -> bool {
+        match self.replay_output.lock().unwrap().as_mut() {
+            Some(output) => {
+                output.push(message.clone());
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Capture a written message if replaying, unless the output is in use or poisoned.
+    /// See [`try_record`](Session::try_record).
+    pub(crate) fn try_capture(&self, message: &rpc::Message) -> bool {
+        match self.replay_output.try_lock().as_deref_mut() {
+            Ok(Some(output)) => {
+                output.push(message.clone());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    #[cfg(any(feature = "tokio", test))]
+    pub(crate) fn start_replay(&self) {
+        *self.replay_output.lock().unwrap() = Some(vec![]);
+    }
+
+    pub(crate) fn take_replay_output(&self) -> Vec<rpc::Message> {
+        self.replay_output
+            .lock()
+            .unwrap()
+            .as_mut()
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{events::Event, Omegga};
+
+    const CHAT: &str = r#"{"jsonrpc":"2.0","method":"chat","params":["x","hello"]}"#;
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("omegga-{}-{}.jsonl", name, std::process::id()))
+    }
+
+    /// Record a plugin that answers a chat message with a broadcast.
+    fn record(name: &str) -> Recording {
+        let path = temp_path(name);
+        let omegga = Omegga::new();
+        omegga.record(&path).unwrap();
+        if let Some(Event::Chat { player, message }) = omegga.handle_line(CHAT) {
+            omegga.broadcast(format!("{}: {}", player, message));
+        }
+        omegga.stop_recording();
+
+        let recording = Recording::load(&path).unwrap();
+        let _ = std::fs::remove_file(path);
+        recording
+    }
+
+    #[test]
+    fn records_messages_in_both_directions() {
+        let recording = record("records");
+        assert_eq!(recording.entries.len(), 2);
+        assert_eq!(recording.incoming().count(), 1);
+        assert_eq!(
+            serde_json::to_string(&recording.incoming().next().unwrap().message).unwrap(),
+            serde_json::to_string(&serde_json::from_str::<rpc::Message>(CHAT).unwrap()).unwrap()
+        );
+
+        let output = recording
+            .outgoing()
+            .map(|e| e.message.clone())
+            .collect::<Vec<_>>();
+        assert!(recording.diff_output(&output).is_empty());
+    }
+
+    #[test]
+    fn reports_differences() {
+        let expected = [rpc::Message::notification(
+            "broadcast".into(),
+            Some("a".into()),
+        )];
+        let actual = [
+            rpc::Message::notification("broadcast".into(), Some("b".into())),
+            rpc::Message::notification("broadcast".into(), Some("c".into())),
+        ];
+
+        let differences = diff(&expected, &actual);
+        assert_eq!(differences.len(), 2);
+        assert_eq!(differences[0].index, 0);
+        assert!(differences[0].expected.is_some() && differences[0].actual.is_some());
+        assert_eq!(differences[1].index, 1);
+        assert_eq!(differences[1].expected, None);
+        assert!(differences[1]
+            .to_string()
+            .starts_with("#1: expected nothing, got "));
+
+        assert_eq!(diff(&actual[..1], &[]).len(), 1);
+        assert!(diff(&expected, &expected).is_empty());
+    }
+
+    #[test]
+    fn skips_the_session_from_the_panic_hook_while_in_use() {
+        let path = temp_path("panics");
+        let omegga = Omegga::new();
+        omegga.record(&path).unwrap();
+        omegga.session.start_replay();
+        let message = rpc::Message::notification("error".into(), Some("panicked".into()));
+
+        let recorder = omegga.session.recorder.lock().unwrap();
+        let output = omegga.session.replay_output.lock().unwrap();
+        // this would deadlock if it waited for either
+        omegga.write_from_panic_hook(message.clone());
+        drop((recorder, output));
+        assert!(omegga.replay_output().is_empty());
+
+        omegga.write_from_panic_hook(message);
+        omegga.stop_recording();
+        assert_eq!(omegga.replay_output().len(), 1);
+        assert_eq!(Recording::load(&path).unwrap().outgoing().count(), 1);
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test]
+    async fn replays_recordings() {
+        let recording = record("replays");
+
+        let omegga = Omegga::new();
+        let mut rx = omegga.replay(recording.clone(), false);
+        while let Some(event) = rx.recv().await {
+            if let Event::Chat { player, message } = event {
+                omegga.broadcast(format!("{}: {}", player, message));
+            }
+        }
+        let output = omegga.replay_output();
+        assert!(recording.diff_output(&output).is_empty());
+        recording.assert_output(&output);
+
+        // a plugin that replies differently no longer matches
+        let omegga = Omegga::new();
+        let mut rx = omegga.replay(recording.clone(), false);
+        while let Some(event) = rx.recv().await {
+            if let Event::Chat { player, .. } = event {
+                omegga.broadcast(format!("{}: bye", player));
+            }
+        }
+        let output = omegga.replay_output();
+        let differences = recording.diff_output(&output);
+        assert_eq!(differences.len(), 1);
+        assert_eq!(differences[0].index, 0);
+
+        let result = std::panic::catch_unwind(|| recording.assert_output(&output));
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,30 @@
+use std::{future::Future, pin::Pin, sync::Mutex};
+
+use crate::Omegga;
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Hook = Box<dyn FnOnce(Omegga) -> BoxFuture + Send>;
+
+/// Async hooks run when the plugin shuts down. See [`Omegga::shutdown`].
+#[derive(Default)]
+pub struct ShutdownHooks {
+    hooks: Mutex<Vec<Hook>>,
+}
+
+impl ShutdownHooks {
+    /// Register a hook.
+    pub fn add<F, Fut>(&self, hook: F)
+    where
+        F: FnOnce(Omegga) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.hooks
+            .lock()
+            .unwrap()
+            .push(Box::new(move |omegga| Box::pin(hook(omegga))));
+    }
+
+    pub(crate) fn take(&self) -> Vec<Hook> {
+        std::mem::take(&mut *self.hooks.lock().unwrap())
+    }
+}
@@ -0,0 +1,399 @@
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use dashmap::DashMap;
+use tokio::{
+    sync::mpsc::{self, UnboundedReceiver},
+    time::{self, Instant, MissedTickBehavior},
+};
+
+use crate::{
+    geometry::{Aabb, Vec3},
+    resources::{Player, PlayerPosition},
+    scheduler::JobHandle,
+    Omegga,
+};
+
+/// Configuration for a [`PositionTracker`].
+#[derive(Debug, Clone)]
+pub struct TrackerConfig {
+    /// How often to poll player positions.
+    pub interval: Duration,
+    /// How far a player must move between polls to count as moving, in world units.
+    pub move_threshold: f64,
+    /// How long a player must not move for to become idle, or `None` to never emit
+    /// [`TrackerEvent::Idle`].
+    pub idle_after: Option<Duration>,
+}
+
+impl Default for TrackerConfig {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_millis(500),
+            move_threshold: 1.0,
+            idle_after: Some(Duration::from_secs(60)),
+        }
+    }
+}
+
+/// An event derived from player positions by a [`PositionTracker`].
+#[derive(Debug, Clone)]
+pub enum TrackerEvent {
+    /// A player moved since the last poll.
+    PlayerMoved {
+        player: Player,
+        from: Vec3,
+        to: Vec3,
+    },
+    /// A player entered a region.
+    EnteredRegion { player: Player, region: String },
+    /// A player left a region, or left the server while in it.
+    LeftRegion { player: Player, region: String },
+    /// A player hasn't moved for the configured idle time.
+    Idle { player: Player, position: Vec3 },
+}
+
+/// The last known state of a tracked player.
+#[derive(Debug, Clone)]
+pub struct TrackedPlayer {
+    pub player: Player,
+    pub position: Vec3,
+    /// When the player last moved.
+    pub last_moved: Instant,
+    /// Whether the player is idle.
+    pub idle: bool,
+    /// The names of the regions the player is in.
+    pub regions: HashSet<String>,
+}
+
+#[derive(Default)]
+struct TrackerState {
+    regions: DashMap<String, Aabb>,
+    players: DashMap<String, TrackedPlayer>,
+}
+
+/// Polls player positions, emitting [`TrackerEvent`]s when players move, idle, or
+/// enter and leave registered regions.
+///
+/// Polling runs as a job on the [`Omegga::scheduler`], so it stops when the plugin is told
+/// to stop, when [`stop`](PositionTracker::stop) is called, or when the event receiver
+/// returned by [`spawn`](PositionTracker::spawn) is dropped.
+#[derive(Clone)]
+pub struct PositionTracker {
+    state: Arc<TrackerState>,
+    job: JobHandle,
+}
+
+impl PositionTracker {
+    /// Spawn a tracker, returning it and a receiver for its events.
+    pub fn spawn(
+        omegga: &Omegga,
+        config: TrackerConfig,
+    ) -> (Self, UnboundedReceiver<TrackerEvent>) {
+        let (tx, rx) = mpsc::unbounded_channel();
+        let state = Arc::new(TrackerState::default());
+        let task_omegga = omegga.clone();
+        let task_state = Arc::clone(&state);
+
+        let job = omegga.scheduler().run(async move {
+            let mut interval = time::interval(config.interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            while !tx.is_closed() {
+                interval.tick().await;
+                let positions = match task_omegga.get_all_player_positions().await {
+                    Ok(positions) => positions,
+                    Err(_) => continue,
+                };
+
+                for event in task_state.update(&config, positions) {
+                    let _ = tx.send(event);
+                }
+            }
+        });
+
+        (Self { state, job }, rx)
+    }
+
+    /// Stop polling. The tracker keeps the last known state of every player.
+    pub fn stop(&self) {
+        self.job.cancel();
+    }
+
+    /// Whether the tracker is still polling.
+    pub fn is_running(&self) -> bool {
+        self.job.is_active()
+    }
+
+    /// Add a region, replacing any existing region with the same name.
+    pub fn add_region(&self, name: impl Into<String>, bounds: Aabb) {
+        self.state.regions.insert(name.into(), bounds);
+    }
+
+    /// Remove a region. Players in it will not receive a [`TrackerEvent::LeftRegion`].
+    pub fn remove_region(&self, name: &str) -> Option<Aabb> {
+        for mut player in self.state.players.iter_mut() {
+            player.regions.remove(name);
+        }
+        self.state.regions.remove(name).map(|(_, bounds)| bounds)
+    }
+
+    /// Get a region by its name.
+    pub fn region(&self, name: &str) -> Option<Aabb> {
+        self.state.regions.get(name).map(|r| *r)
+    }
+
+    /// Get the last known state of a player by their ID.
+    pub fn player(&self, id: &str) -> Option<TrackedPlayer> {
+        self.state.players.get(id).map(|p| p.clone())
+    }
+
+    /// Get the last known state of every player.
+    pub fn players(&self) -> Vec<TrackedPlayer> {
+        self.state.players.iter().map(|p| p.clone()).collect()
+    }
+
+    /// Get the last known state of every player in a region.
+    pub fn players_in(&self, region: &str) -> Vec<TrackedPlayer> {
+        self.state
+            .players
+            .iter()
+            .filter(|p| p.regions.contains(region))
+            .map(|p| p.clone())
+            .collect()
+    }
+}
+
+impl TrackerState {
+    fn regions_at(&self, position: Vec3) -> HashSet<String> {
+        self.regions
+            .iter()
+            .filter(|r| r.value().contains(position))
+            .map(|r| r.key().clone())
+            .collect()
+    }
+
+    fn update(&self, config: &TrackerConfig, positions: Vec<PlayerPosition>) -> Vec<TrackerEvent> {
+        let now = Instant::now();
+        let mut events = vec![];
+        let mut seen = HashSet::new();
+
+        for position in positions {
+            // players without a position (like dead players) keep their last known state
+            seen.insert(position.player.id.clone());
+            let (player, pos) = match position.pos {
+                Some(pos) => (position.player, pos),
+                None => continue,
+            };
+            let regions = self.regions_at(pos);
+
+            let mut tracked = match self.players.get_mut(&player.id) {
+                Some(tracked) => tracked,
+                None => {
+                    for region in &regions {
+                        events.push(TrackerEvent::EnteredRegion {
+                            player: player.clone(),
+                            region: region.clone(),
+                        });
+                    }
+                    self.players.insert(
+                        player.id.clone(),
+                        TrackedPlayer {
+                            player,
+                            position: pos,
+                            last_moved: now,
+                            idle: false,
+                            regions,
+                        },
+                    );
+                    continue;
+                }
+            };
+
+            tracked.player = player.clone();
+            if tracked.position.distance(pos) >= config.move_threshold {
+                events.push(TrackerEvent::PlayerMoved {
+                    player: player.clone(),
+                    from: tracked.position,
+                    to: pos,
+                });
+                tracked.position = pos;
+                tracked.last_moved = now;
+                tracked.idle = false;
+            } else if let Some(idle_after) = config.idle_after {
+                if !tracked.idle && now.duration_since(tracked.last_moved) >= idle_after {
+                    tracked.idle = true;
+                    events.push(TrackerEvent::Idle {
+                        player: player.clone(),
+                        position: tracked.position,
+                    });
+                }
+            }
+
+            for region in tracked.regions.difference(&regions) {
+                events.push(TrackerEvent::LeftRegion {
+                    player: player.clone(),
+                    region: region.clone(),
+                });
+            }
+            for region in regions.difference(&tracked.regions) {
+                events.push(TrackerEvent::EnteredRegion {
+                    player: player.clone(),
+                    region: region.clone(),
+                });
+            }
+            tracked.regions = regions;
+        }
+
+        // players that are no longer on the server leave their regions
+        self.players.retain(|id, tracked| {
+            if seen.contains(id) {
+                return true;
+            }
+            for region in &tracked.regions {
+                events.push(TrackerEvent::LeftRegion {
+                    player: tracked.player.clone(),
+                    region: region.clone(),
+                });
+            }
+            false
+        });
+
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(id: &str) -> Player {
+        Player {
+            name: id.to_string(),
+            id: id.to_string(),
+            controller: format!("{}-controller", id),
+            state: format!("{}-state", id),
+            host: None,
+        }
+    }
+
+    fn at(id: &str, x: f64) -> PlayerPosition {
+        PlayerPosition {
+            player: player(id),
+            pos: Some(Vec3::new(x, 0.0, 0.0)),
+        }
+    }
+
+    fn state() -> TrackerState {
+        let state = TrackerState::default();
+        state.regions.insert(
+            "spawn".into(),
+            Aabb::new((0.0, -5.0, -5.0), (10.0, 5.0, 5.0)),
+        );
+        state
+    }
+
+    fn config() -> TrackerConfig {
+        TrackerConfig {
+            move_threshold: 2.0,
+            idle_after: None,
+            ..TrackerConfig::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn tracks_joining_players() {
+        let state = state();
+        assert!(state.update(&config(), vec![at("a", 100.0)]).is_empty());
+        assert_eq!(state.players.get("a").unwrap().position.x, 100.0);
+
+        let events = state.update(&config(), vec![at("a", 100.0), at("b", 5.0)]);
+        assert!(matches!(
+            &events[..],
+            [TrackerEvent::EnteredRegion { player, region }] if player.id == "b" && region == "spawn"
+        ));
+    }
+
+    #[tokio::test]
+    async fn ignores_small_moves() {
+        let state = state();
+        state.update(&config(), vec![at("a", 100.0)]);
+        assert!(state.update(&config(), vec![at("a", 101.5)]).is_empty());
+        assert_eq!(state.players.get("a").unwrap().position.x, 100.0);
+
+        let events = state.update(&config(), vec![at("a", 102.0)]);
+        assert!(matches!(
+            &events[..],
+            [TrackerEvent::PlayerMoved { from, to, .. }] if from.x == 100.0 && to.x == 102.0
+        ));
+        assert_eq!(state.players.get("a").unwrap().position.x, 102.0);
+    }
+
+    #[tokio::test]
+    async fn enters_and_leaves_regions() {
+        let state = state();
+        state.update(&config(), vec![at("a", 20.0)]);
+
+        let events = state.update(&config(), vec![at("a", 10.0)]);
+        assert!(matches!(
+            &events[..],
+            [TrackerEvent::PlayerMoved { .. }, TrackerEvent::EnteredRegion { region, .. }]
+                if region == "spawn"
+        ));
+        assert!(state.players.get("a").unwrap().regions.contains("spawn"));
+
+        let events = state.update(&config(), vec![at("a", 12.0)]);
+        assert!(matches!(
+            &events[..],
+            [TrackerEvent::PlayerMoved { .. }, TrackerEvent::LeftRegion { region, .. }]
+                if region == "spawn"
+        ));
+        assert!(state.players.get("a").unwrap().regions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn leaves_regions_when_leaving_the_server() {
+        let state = state();
+        state.update(&config(), vec![at("a", 5.0), at("b", 50.0)]);
+
+        let events = state.update(&config(), vec![at("b", 50.0)]);
+        assert!(matches!(
+            &events[..],
+            [TrackerEvent::LeftRegion { player, region }] if player.id == "a" && region == "spawn"
+        ));
+        assert!(state.players.get("a").is_none());
+    }
+
+    #[tokio::test]
+    async fn keeps_players_without_a_position() {
+        let state = state();
+        state.update(&config(), vec![at("a", 5.0)]);
+
+        let dead = PlayerPosition {
+            player: player("a"),
+            pos: None,
+        };
+        assert!(state.update(&config(), vec![dead]).is_empty());
+        assert!(state.players.get("a").unwrap().regions.contains("spawn"));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn idles_once() {
+        let config = TrackerConfig {
+            idle_after: Some(Duration::from_secs(60)),
+            ..config()
+        };
+        let state = state();
+        state.update(&config, vec![at("a", 50.0)]);
+
+        time::advance(Duration::from_secs(59)).await;
+        assert!(state.update(&config, vec![at("a", 50.0)]).is_empty());
+
+        time::advance(Duration::from_secs(1)).await;
+        let events = state.update(&config, vec![at("a", 50.0)]);
+        assert!(matches!(&events[..], [TrackerEvent::Idle { .. }]));
+        assert!(state.update(&config, vec![at("a", 50.0)]).is_empty());
+
+        state.update(&config, vec![at("a", 60.0)]);
+        assert!(!state.players.get("a").unwrap().idle);
+    }
+}
@@ -0,0 +1,449 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{Arc, Mutex, RwLock, Weak},
+};
+
+use dashmap::DashMap;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
+
+use crate::{
+    geometry::{Aabb, Vec3},
+    resources::Player,
+    tracker::{PositionTracker, TrackerConfig, TrackerEvent},
+    Omegga, ResponseError,
+};
+
+type BoxFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
+type Handler = Arc<dyn Fn(ZoneContext) -> BoxFuture + Send + Sync>;
+
+/// A named 3D region, like a spawn area or a minigame arena.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    pub bounds: Aabb,
+    /// Any extra data a plugin wants to keep with the zone.
+    #[serde(default)]
+    pub data: Value,
+}
+
+impl Zone {
+    /// Create a new zone.
+    pub fn new(name: impl Into<String>, bounds: Aabb) -> Self {
+        Self {
+            name: name.into(),
+            bounds,
+            data: Value::Null,
+        }
+    }
+
+    /// Set the zone's extra data.
+    pub fn with_data(mut self, data: Value) -> Self {
+        self.data = data;
+        self
+    }
+}
+
+/// Whether a player entered or left a zone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transition {
+    Enter,
+    Exit,
+}
+
+/// The context a zone handler is called with.
+#[derive(Clone)]
+pub struct ZoneContext {
+    pub omegga: Omegga,
+    pub player: Player,
+    pub zone: Zone,
+    pub transition: Transition,
+}
+
+struct ZoneHandler {
+    zone: String,
+    transition: Transition,
+    handler: Handler,
+}
+
+struct ZonesInner {
+    omegga: Omegga,
+    tracker: PositionTracker,
+    zones: DashMap<String, Zone>,
+    handlers: RwLock<Vec<ZoneHandler>>,
+    store_key: Mutex<Option<String>>,
+}
+
+impl Drop for ZonesInner {
+    fn drop(&mut self) {
+        self.tracker.stop();
+    }
+}
+
+/// A set of named zones, backed by a [`PositionTracker`].
+///
+/// Handlers registered with [`on_enter`](Zones::on_enter) and [`on_exit`](Zones::on_exit)
+/// are spawned as Tokio tasks when players enter or leave zones. The tracker stops polling
+/// when every clone of the zones is dropped, when [`stop`](Zones::stop) is called, or when
+/// the plugin is told to stop.
+#[derive(Clone)]
+pub struct Zones {
+    inner: Arc<ZonesInner>,
+}
+
+impl Zones {
+    /// Spawn a position tracker for zones, returning the zones and a receiver for every
+    /// [`TrackerEvent`] the tracker emits.
+    pub fn spawn(
+        omegga: &Omegga,
+        config: TrackerConfig,
+    ) -> (Self, UnboundedReceiver<TrackerEvent>) {
+        let (tracker, mut tracker_rx) = PositionTracker::spawn(omegga, config);
+        let (tx, rx) = mpsc::unbounded_channel();
+        let zones = Self {
+            inner: Arc::new(ZonesInner {
+                omegga: omegga.clone(),
+                tracker,
+                zones: DashMap::new(),
+                handlers: RwLock::new(vec![]),
+                store_key: Mutex::new(None),
+            }),
+        };
+
+        // the dispatcher holds the zones weakly, so dropping them stops the tracker
+        let dispatcher = Arc::downgrade(&zones.inner);
+        omegga.spawn_task(async move {
+            while let Some(event) = tracker_rx.recv().await {
+                let dispatcher = match Weak::upgrade(&dispatcher) {
+                    Some(inner) => Zones { inner },
+                    None => break,
+                };
+                match &event {
+                    TrackerEvent::EnteredRegion { player, region } => {
+                        dispatcher.dispatch(player, region, Transition::Enter)
+                    }
+                    TrackerEvent::LeftRegion { player, region } => {
+                        dispatcher.dispatch(player, region, Transition::Exit)
+                    }
+                    _ => (),
+                }
+
+                // handlers still run if the receiver has been dropped
+                let _ = tx.send(event);
+            }
+        });
+
+        (zones, rx)
+    }
+
+    /// Stop the tracker backing the zones. Zones can still be defined and queried, but
+    /// handlers will no longer run.
+    pub fn stop(&self) {
+        self.inner.tracker.stop();
+    }
+
+    fn dispatch(&self, player: &Player, zone: &str, transition: Transition) {
+        let zone = match self.get(zone) {
+            Some(zone) => zone,
+            None => return,
+        };
+
+        let handlers = self.inner.handlers.read().unwrap();
+        for h in handlers.iter() {
+            if h.transition == transition && (h.zone == "*" || h.zone == zone.name) {
+                self.inner.omegga.spawn_task((h.handler)(ZoneContext {
+                    omegga: self.inner.omegga.clone(),
+                    player: player.clone(),
+                    zone: zone.clone(),
+                    transition,
+                }));
+            }
+        }
+    }
+
+    fn add_handler<F, Fut>(&self, zone: String, transition: Transition, handler: F)
+    where
+        F: Fn(ZoneContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.inner.handlers.write().unwrap().push(ZoneHandler {
+            zone,
+            transition,
+            handler: Arc::new(move |ctx| Box::pin(handler(ctx))),
+        });
+    }
+
+    /// Run a handler when a player enters a zone, by its name, or any zone with `"*"`.
+    pub fn on_enter<F, Fut>(&self, zone: impl Into<String>, handler: F)
+    where
+        F: Fn(ZoneContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.add_handler(zone.into(), Transition::Enter, handler);
+    }
+
+    /// Run a handler when a player leaves a zone, by its name, or any zone with `"*"`.
+    /// Players leaving the server while in a zone also leave it.
+    pub fn on_exit<F, Fut>(&self, zone: impl Into<String>, handler: F)
+    where
+        F: Fn(ZoneContext) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        self.add_handler(zone.into(), Transition::Exit, handler);
+    }
+
+    /// Define a zone, replacing any existing zone with the same name.
+    pub fn define(&self, zone: Zone) {
+        self.inner
+            .tracker
+            .add_region(zone.name.as_str(), zone.bounds);
+        self.inner.zones.insert(zone.name.clone(), zone);
+        self.autosave();
+    }
+
+    /// Remove a zone by its name.
+    pub fn remove(&self, name: &str) -> Option<Zone> {
+        self.inner.tracker.remove_region(name);
+        let zone = self.inner.zones.remove(name).map(|(_, zone)| zone);
+        self.autosave();
+        zone
+    }
+
+    /// Get a zone by its name.
+    pub fn get(&self, name: &str) -> Option<Zone> {
+        self.inner.zones.get(name).map(|z| z.clone())
+    }
+
+    /// Get every zone.
+    pub fn all(&self) -> Vec<Zone> {
+        self.inner.zones.iter().map(|z| z.clone()).collect()
+    }
+
+    /// Get every zone containing a point.
+    pub fn zones_at(&self, point: impl Into<Vec3>) -> Vec<Zone> {
+        let point = point.into();
+        self.inner
+            .zones
+            .iter()
+            .filter(|z| z.bounds.contains(point))
+            .map(|z| z.clone())
+            .collect()
+    }
+
+    /// Get every zone a player is in, by their ID, as of the last poll.
+    pub fn zones_of(&self, id: &str) -> Vec<Zone> {
+        self.inner
+            .tracker
+            .player(id)
+            .map(|p| p.regions.iter().filter_map(|r| self.get(r)).collect())
+            .unwrap_or_default()
+    }
+
+    /// Get every player in a zone, as of the last poll.
+    pub fn players_in(&self, name: &str) -> Vec<Player> {
+        self.inner
+            .tracker
+            .players_in(name)
+            .into_iter()
+            .map(|p| p.player)
+            .collect()
+    }
+
+    /// The position tracker backing the zones.
+    pub fn tracker(&self) -> &PositionTracker {
+        &self.inner.tracker
+    }
+
+    /// Save every zone to the store under `key`.
+    pub fn save(&self, key: impl Into<String>) {
+        let zones = self.all();
+        self.inner.omegga.store_set(
+            key,
+            serde_json::to_value(zones).unwrap_or_else(|_| Value::Array(vec![])),
+        );
+    }
+
+    /// Load zones from the store under `key`, defining each of them.
+    pub async fn load(&self, key: impl Into<String>) -> Result<(), ResponseError> {
+        let zones = self
+            .inner
+            .omegga
+            .store_get(key)
+            .await?
+            .and_then(|v| serde_json::from_value::<Vec<Zone>>(v).ok())
+            .unwrap_or_default();
+
+        for zone in zones {
+            self.inner
+                .tracker
+                .add_region(zone.name.as_str(), zone.bounds);
+            self.inner.zones.insert(zone.name.clone(), zone);
+        }
+        Ok(())
+    }
+
+    /// Load zones from the store under `key`, then save them back to it whenever
+    /// a zone is defined or removed.
+    pub async fn persist(&self, key: impl Into<String>) -> Result<(), ResponseError> {
+        let key = key.into();
+        self.load(key.as_str()).await?;
+        *self.inner.store_key.lock().unwrap() = Some(key);
+        Ok(())
+    }
+
+    fn autosave(&self) {
+        let key = self.inner.store_key.lock().unwrap().clone();
+        if let Some(key) = key {
+            self.save(key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use serde_json::json;
+
+    use super::*;
+    use crate::rpc;
+
+    /// An [`Omegga`] that keeps what it writes instead of printing it.
+    fn omegga() -> Omegga {
+        let omegga = Omegga::new();
+        omegga.session.start_replay();
+        omegga
+    }
+
+    fn config() -> TrackerConfig {
+        TrackerConfig {
+            interval: Duration::from_secs(3600),
+            ..TrackerConfig::default()
+        }
+    }
+
+    fn player() -> Player {
+        Player {
+            name: "x".into(),
+            id: "x-id".into(),
+            controller: "x-controller".into(),
+            state: "x-state".into(),
+            host: None,
+        }
+    }
+
+    fn zone(name: &str) -> Zone {
+        Zone::new(name, Aabb::new((0.0, 0.0, 0.0), (10.0, 10.0, 10.0)))
+    }
+
+    /// The zones saved to the store by each `store.set` written.
+    fn saved(omegga: &Omegga) -> Vec<Vec<String>> {
+        omegga
+            .replay_output()
+            .into_iter()
+            .filter_map(|message| match message {
+                rpc::Message::Notification { method, params, .. } if method == "store.set" => {
+                    let [key, zones] = serde_json::from_value::<[Value; 2]>(params?).ok()?;
+                    assert_eq!(key, "zones");
+                    let mut names = serde_json::from_value::<Vec<Zone>>(zones)
+                        .ok()?
+                        .into_iter()
+                        .map(|z| z.name)
+                        .collect::<Vec<_>>();
+                    names.sort();
+                    Some(names)
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn persists_zones() {
+        let omegga = omegga();
+        let (zones, _rx) = Zones::spawn(&omegga, config());
+        let persisting = tokio::spawn({
+            let zones = zones.clone();
+            async move { zones.persist("zones").await }
+        });
+
+        let id = loop {
+            tokio::task::yield_now().await;
+            let id = omegga.replay_output().into_iter().find_map(|m| match m {
+                rpc::Message::Request { id, method, .. } if method == "store.get" => Some(id),
+                _ => None,
+            });
+            if let Some(id) = id {
+                break id;
+            }
+        };
+        omegga.handle_message(rpc::Message::response(
+            id,
+            Some(json!([zone("spawn").with_data(json!({ "team": 1 }))])),
+            None,
+        ));
+        persisting.await.unwrap().unwrap();
+
+        // loading doesn't save, but defining and removing zones does
+        assert_eq!(zones.get("spawn").unwrap().data, json!({ "team": 1 }));
+        assert!(zones.tracker().region("spawn").is_some());
+        assert!(saved(&omegga).is_empty());
+
+        zones.define(zone("arena"));
+        assert_eq!(saved(&omegga), [["arena", "spawn"]]);
+        zones.remove("spawn");
+        assert_eq!(saved(&omegga), [["arena"]]);
+        assert!(zones.tracker().region("spawn").is_none());
+    }
+
+    #[tokio::test]
+    async fn dispatches_to_named_and_wildcard_handlers() {
+        let omegga = omegga();
+        let (zones, _rx) = Zones::spawn(&omegga, config());
+        zones.define(zone("spawn"));
+        zones.define(zone("arena"));
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        for (name, transition) in [
+            ("spawn", Transition::Enter),
+            ("arena", Transition::Enter),
+            ("*", Transition::Enter),
+            ("*", Transition::Exit),
+        ] {
+            let tx = tx.clone();
+            let handler = move |ctx: ZoneContext| {
+                let tx = tx.clone();
+                async move {
+                    let _ = tx.send((name, ctx.zone.name, ctx.transition));
+                }
+            };
+            match transition {
+                Transition::Enter => zones.on_enter(name, handler),
+                Transition::Exit => zones.on_exit(name, handler),
+            }
+        }
+        drop(tx);
+
+        zones.dispatch(&player(), "spawn", Transition::Enter);
+        zones.dispatch(&player(), "arena", Transition::Exit);
+        // zones that aren't defined don't run handlers, even wildcard ones
+        zones.dispatch(&player(), "missing", Transition::Enter);
+        drop(zones);
+
+        let mut calls = vec![];
+        while let Some(call) = rx.recv().await {
+            calls.push(call);
+        }
+        calls.sort_by_key(|(name, zone, _)| (*name, zone.clone()));
+        assert_eq!(
+            calls,
+            [
+                ("*", "arena".to_string(), Transition::Exit),
+                ("*", "spawn".to_string(), Transition::Enter),
+                ("spawn", "spawn".to_string(), Transition::Enter),
+            ]
+        );
+    }
+}